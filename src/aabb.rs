@@ -17,6 +17,11 @@ impl AABB {
     }
 
     pub fn hit(&self, r: &Ray, tmin: f64, tmax: f64) -> bool {
+        self.interval(r, tmin, tmax).is_some()
+    }
+
+    // The part of (tmin, tmax) where the ray is inside the box
+    pub fn interval(&self, r: &Ray, mut tmin: f64, mut tmax: f64) -> Option<(f64, f64)> {
         for a in 0..3 {
             let inv_d = 1.0 / r.direction[a];
             let mut t0 = (self.min[a] - r.origin[a]) * inv_d;
@@ -25,15 +30,15 @@ impl AABB {
                 swap(&mut t0, &mut t1);
             }
 
-            let tmin = t0.max(tmin);
-            let tmax = t1.min(tmax);
+            tmin = t0.max(tmin);
+            tmax = t1.min(tmax);
 
             if tmax <= tmin {
-                return false;
+                return None;
             }
         }
 
-        true
+        Some((tmin, tmax))
     }
 
     pub fn surrounding_box(&self, other: &AABB) -> Self {
@@ -50,4 +55,17 @@ impl AABB {
 
         AABB::new(small, big)
     }
+
+    pub fn pad(&self, delta: f64) -> Self {
+        let mut min = self.min;
+        let mut max = self.max;
+        for a in 0..3 {
+            if max[a] - min[a] < delta {
+                min[a] -= delta / 2.0;
+                max[a] += delta / 2.0;
+            }
+        }
+
+        AABB::new(min, max)
+    }
 }
@@ -7,16 +7,19 @@ use rand::prelude::*;
 
 use std::cmp::Ordering;
 
-pub struct BvhTree<'a> {
-    nodes: Vec<BvhNode<'a>>,
-    root: NodeId,
+pub struct BvhTree<T: Hittable = Box<dyn Hittable>> {
+    nodes: Vec<BvhNode>,
+    hittables: Vec<T>,
+    root: Option<NodeId>,
+    // Objects without a bounding box, like infinite planes, are tested against every ray
+    unbounded: Vec<T>,
 }
 
-struct BvhNode<'a> {
+struct BvhNode {
     left: Option<NodeId>,
     right: Option<NodeId>,
-    aabb: Option<AABB>,
-    hittable: Option<&'a Box<Hittable>>,
+    aabb: AABB,
+    hittable: Option<usize>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -24,116 +27,127 @@ pub struct NodeId {
     index: usize,
 }
 
-impl<'a> BvhTree<'a> {
+impl<T: Hittable> BvhTree<T> {
     fn hit(&self, id: NodeId, r: &Ray, tmin: f64, tmax: f64) -> Option<HitRecord> {
         let node = &self.nodes[id.index];
 
-        if node.aabb.is_none() || node.aabb.is_some() && node.aabb.unwrap().hit(r, tmin, tmax) {
-            if let Some(ref hitable) = node.hittable {
-                return hitable.hits(r, tmin, tmax);
+        if node.aabb.hit(r, tmin, tmax) {
+            if let Some(index) = node.hittable {
+                return self.hittables[index].hits(r, tmin, tmax);
             }
 
             let mut hit_left: Option<HitRecord> = None;
             let mut hit_right: Option<HitRecord> = None;
 
-            if let Some(ref left_index) = node.left {
-                hit_left = self.hit(*left_index, r, tmin, tmax);
+            if let Some(left_index) = node.left {
+                hit_left = self.hit(left_index, r, tmin, tmax);
             }
 
-            if let Some(ref right_index) = node.right {
-                hit_right = self.hit(*right_index, r, tmin, tmax);
-            }
-
-            if let Some(left) = hit_left {
-                match hit_right {
-                    Some(right) => {
-                        if left.t < right.t {
-                            return Some(left);
-                        } else {
-                            return Some(right);
-                        }
-                    }
-                    None => return Some(left),
-                }
+            let tmax = hit_left.as_ref().map_or(tmax, |hit| hit.t);
+            if let Some(right_index) = node.right {
+                hit_right = self.hit(right_index, r, tmin, tmax);
             }
 
             if hit_right.is_some() {
                 return hit_right;
             }
+
+            return hit_left;
         }
 
         None
     }
 }
 
-impl<'a> Hittable for BvhTree<'a> {
+impl<T: Hittable> Hittable for BvhTree<T> {
     fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
-        self.nodes[self.root.index].aabb
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+
+        self.root.map(|root| self.nodes[root.index].aabb)
     }
 
     fn hits(&self, r: &Ray, tmin: f64, tmax: f64) -> Option<HitRecord> {
-        self.hit(self.root, r, tmin, tmax)
+        let mut hit = self.root.and_then(|root| self.hit(root, r, tmin, tmax));
+        for hittable in &self.unbounded {
+            let tmax = hit.as_ref().map_or(tmax, |hit| hit.t);
+            if let Some(unbounded_hit) = hittable.hits(r, tmin, tmax) {
+                hit = Some(unbounded_hit);
+            }
+        }
+
+        hit
     }
 }
 
-impl<'a> BvhTree<'a> {
-    pub fn new(l: &'a mut [Box<Hittable>], time0: f64, time1: f64) -> BvhTree<'a> {
+impl<T: Hittable> BvhTree<T> {
+    pub fn new(hittables: Vec<T>, time0: f64, time1: f64) -> BvhTree<T> {
+        let mut items: Vec<(AABB, T)> = Vec::with_capacity(hittables.len());
+        let mut unbounded = Vec::new();
+        for hittable in hittables {
+            match hittable.bounding_box(time0, time1) {
+                Some(aabb) => items.push((aabb, hittable)),
+                None => unbounded.push(hittable),
+            }
+        }
+
         let mut tree = BvhTree {
-            nodes: Vec::new(),
-            root: NodeId { index: 0 },
+            nodes: Vec::with_capacity(2 * items.len()),
+            hittables: Vec::new(),
+            root: None,
+            unbounded,
         };
-        tree.root = tree.build(l, time0, time1);
+        if !items.is_empty() {
+            tree.root = Some(tree.build(&mut items, 0));
+        }
+        tree.hittables = items.into_iter().map(|(_, hittable)| hittable).collect();
 
         tree
     }
 
-    fn build(&mut self, l: &'a mut [Box<Hittable>], time0: f64, time1: f64) -> NodeId {
-        let axis = thread_rng().gen_range::<u32>(0, 3);
+    pub fn len(&self) -> usize {
+        self.hittables.len() + self.unbounded.len()
+    }
 
-        match axis {
-            0 => l.sort_by(|a, b| box_x_compare(a, b, time0, time1)),
-            1 => l.sort_by(|a, b| box_y_compare(a, b, time0, time1)),
-            2 => l.sort_by(|a, b| box_z_compare(a, b, time0, time1)),
-            _ => panic!("Wait what? How did this happen... "),
-        }
+    pub fn is_empty(&self) -> bool {
+        self.hittables.is_empty() && self.unbounded.is_empty()
+    }
+
+    fn build(&mut self, l: &mut [(AABB, T)], offset: usize) -> NodeId {
+        let axis = thread_rng().gen_range::<usize>(0, 3);
+        l.sort_by(|a, b| box_compare(&a.0, &b.0, axis));
 
         let left: NodeId;
         let right: NodeId;
 
         if l.len() == 1 {
-            return self.new_leaf(&l[0], time0, time1);
+            return self.new_leaf(l[0].0, offset);
         } else if l.len() == 2 {
-            left = self.new_leaf(&l[0], time0, time1);
-            right = self.new_leaf(&l[1], time0, time1);
+            left = self.new_leaf(l[0].0, offset);
+            right = self.new_leaf(l[1].0, offset + 1);
         } else {
             let half_len = l.len() / 2;
             let (left_hitables, right_hitables) = l.split_at_mut(half_len);
 
-            left = self.build(left_hitables, time0, time1);
-            right = self.build(right_hitables, time0, time1);
+            left = self.build(left_hitables, offset);
+            right = self.build(right_hitables, offset + half_len);
         }
 
-        if let Some(left_box) = self.nodes[left.index].aabb {
-            if let Some(right_box) = self.nodes[right.index].aabb {
-                return self.new_node(
-                    left_box.surrounding_box(&right_box),
-                    Some(left),
-                    Some(right),
-                );
-            }
-        }
-
-        panic!("No bounding box in BvhNode::build");
+        let aabb = self.nodes[left.index]
+            .aabb
+            .surrounding_box(&self.nodes[right.index].aabb);
+        self.new_node(aabb, Some(left), Some(right))
     }
 
-    fn new_leaf(&mut self, hitable: &'a Box<Hittable>, time0: f64, time1: f64) -> NodeId {
+    fn new_leaf(&mut self, aabb: AABB, hittable: usize) -> NodeId {
         let index = self.nodes.len();
 
         self.nodes.push(BvhNode {
             left: None,
             right: None,
-            aabb: hitable.bounding_box(time0, time1),
-            hittable: Some(hitable),
+            aabb,
+            hittable: Some(hittable),
         });
 
         NodeId { index }
@@ -145,7 +159,7 @@ impl<'a> BvhTree<'a> {
         self.nodes.push(BvhNode {
             left,
             right,
-            aabb: Some(aabb),
+            aabb,
             hittable: None,
         });
 
@@ -153,44 +167,7 @@ impl<'a> BvhTree<'a> {
     }
 }
 
-fn box_x_compare(a: &Box<Hittable>, b: &Box<Hittable>, time0: f64, time1: f64) -> Ordering {
-    if let Some(box_left) = a.bounding_box(time0, time1) {
-        if let Some(box_right) = b.bounding_box(time0, time1) {
-            if let Some(cmp) = box_left.min.x.partial_cmp(&box_right.min.x) {
-                return cmp;
-            } else {
-                panic!("Can't compare");
-            }
-        }
-    }
-
-    panic!("No bounding box in BvhNode::new");
-}
-
-fn box_y_compare(a: &Box<Hittable>, b: &Box<Hittable>, time0: f64, time1: f64) -> Ordering {
-    if let Some(box_left) = a.bounding_box(time0, time1) {
-        if let Some(box_right) = b.bounding_box(time0, time1) {
-            if let Some(cmp) = box_left.min.y.partial_cmp(&box_right.min.y) {
-                return cmp;
-            } else {
-                panic!("Can't compare");
-            }
-        }
-    }
-
-    panic!("No bounding box in BvhNode::new");
-}
-
-fn box_z_compare(a: &Box<Hittable>, b: &Box<Hittable>, time0: f64, time1: f64) -> Ordering {
-    if let Some(box_left) = a.bounding_box(time0, time1) {
-        if let Some(box_right) = b.bounding_box(time0, time1) {
-            if let Some(cmp) = box_left.min.z.partial_cmp(&box_right.min.z) {
-                return cmp;
-            } else {
-                panic!("Can't compare");
-            }
-        }
-    }
-
-    panic!("No bounding box in BvhNode::new");
+// Orders NaN bounds too, which degenerate objects can have, so building never panics
+fn box_compare(box_left: &AABB, box_right: &AABB, axis: usize) -> Ordering {
+    box_left.min[axis].total_cmp(&box_right.min[axis])
 }
@@ -0,0 +1,457 @@
+use super::invalid_data;
+use crate::materials::conductor::Conductor;
+use crate::materials::dielectric::Dielectric;
+use crate::materials::lambertian::Lambertian;
+use crate::materials::light::DiffuseLight;
+use crate::materials::Material;
+use crate::objects::camera::Camera;
+use crate::objects::mesh::Mesh;
+use crate::objects::mesh::TriangleMesh;
+use crate::objects::HittableList;
+use crate::textures::constant_texture::ConstantTexture;
+use crate::textures::image_texture::ImageTexture;
+use crate::textures::Texture;
+
+use ::gltf::camera::Projection;
+use ::gltf::image::Format;
+use ::gltf::mesh::Mode;
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::vec4;
+use cgmath::Matrix3;
+use cgmath::Matrix4;
+use cgmath::Point3;
+use cgmath::Vector3;
+use image::Rgb;
+use image::RgbImage;
+
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+pub struct GltfScene {
+    pub objects: HittableList,
+    pub cameras: Vec<Camera>,
+    // Parts of the file that could only be approximated, for the caller to report
+    pub warnings: Vec<String>,
+}
+
+// Loads the default scene of a .gltf or .glb file. Node transforms are baked into the mesh
+// vertices and cameras are built with the aspect ratio of the image being rendered.
+// Refer: https://registry.khronos.org/glTF/specs/2.0/glTF-2.0.html
+pub fn load<P: AsRef<Path>>(path: P, aspect: f64) -> io::Result<GltfScene> {
+    let path = path.as_ref();
+    let (document, buffers, images) =
+        ::gltf::import(path).map_err(|e| invalid_data(format!("{}: {}", path.display(), e)))?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| invalid_data(format!("{}: no scene", path.display())))?;
+
+    load_scene(&scene, buffers, images, aspect)
+}
+
+fn load_scene(
+    scene: &::gltf::Scene,
+    buffers: Vec<::gltf::buffer::Data>,
+    images: Vec<::gltf::image::Data>,
+    aspect: f64,
+) -> io::Result<GltfScene> {
+    let mut loader = Loader {
+        buffers,
+        images,
+        textures: HashMap::new(),
+        materials: HashMap::new(),
+        aspect,
+        scene: GltfScene {
+            objects: HittableList::new(),
+            cameras: Vec::new(),
+            warnings: Vec::new(),
+        },
+    };
+    for node in scene.nodes() {
+        loader.visit(&node, Matrix4::identity())?;
+    }
+
+    Ok(loader.scene)
+}
+
+struct Loader {
+    buffers: Vec<::gltf::buffer::Data>,
+    images: Vec<::gltf::image::Data>,
+    textures: HashMap<usize, Arc<RgbImage>>,
+    materials: HashMap<Option<usize>, Arc<Material>>,
+    aspect: f64,
+    scene: GltfScene,
+}
+
+impl Loader {
+    fn visit(&mut self, node: &::gltf::Node, parent: Matrix4<f64>) -> io::Result<()> {
+        let local = node.transform().matrix();
+        let transform = parent * Matrix4::from(local).cast::<f64>().unwrap();
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                if let Some(triangles) = self.primitive(&primitive, transform)? {
+                    self.scene.objects.add(Box::new(Mesh::new(triangles)));
+                }
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            if let Projection::Perspective(perspective) = camera.projection() {
+                let origin = Point3::from_homogeneous(transform * vec4(0.0, 0.0, 0.0, 1.0));
+                let forward = (transform * vec4(0.0, 0.0, -1.0, 0.0)).truncate();
+                let up = (transform * vec4(0.0, 1.0, 0.0, 0.0)).truncate();
+                self.scene.cameras.push(Camera::new(
+                    origin,
+                    origin + forward,
+                    up,
+                    f64::from(perspective.yfov()).to_degrees(),
+                    self.aspect,
+                    0.0,
+                    1.0,
+                ));
+            }
+        }
+
+        for child in node.children() {
+            self.visit(&child, transform)?;
+        }
+
+        Ok(())
+    }
+
+    fn primitive(
+        &mut self,
+        primitive: &::gltf::Primitive,
+        transform: Matrix4<f64>,
+    ) -> io::Result<Option<TriangleMesh>> {
+        let mode = primitive.mode();
+        if mode != Mode::Triangles && mode != Mode::TriangleStrip && mode != Mode::TriangleFan {
+            return Ok(None);
+        }
+
+        let buffers = &self.buffers;
+        let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+        let positions: Vec<Point3<f64>> = match reader.read_positions() {
+            Some(positions) => positions
+                .map(|p| transform.transform_point(to_point(p)))
+                .collect(),
+            None => return Ok(None),
+        };
+
+        let linear = Matrix3::from_cols(
+            transform.x.truncate(),
+            transform.y.truncate(),
+            transform.z.truncate(),
+        );
+        let normal_matrix = linear.invert().map(|m| m.transpose()).unwrap_or(linear);
+        let normals = reader.read_normals().map(|normals| {
+            normals
+                .map(|n| (normal_matrix * to_vector(n)).normalize())
+                .collect()
+        });
+        // glTF puts the texture origin at the top left, textures here sample from the bottom left
+        let uvs = reader.read_tex_coords(0).map(|uvs| {
+            uvs.into_f32()
+                .map(|[u, v]| (f64::from(u), 1.0 - f64::from(v)))
+                .collect()
+        });
+        let colors = reader
+            .read_colors(0)
+            .map(|colors| colors.into_rgb_f32().map(to_vector).collect());
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if indices.iter().any(|&i| i as usize >= positions.len()) {
+            return Err(invalid_data("glTF primitive index out of range"));
+        }
+
+        let material = self.material(&primitive.material())?;
+        let mut mesh = TriangleMesh::new(material);
+        mesh.positions = positions;
+        mesh.normals = normals;
+        mesh.uvs = uvs;
+        mesh.colors = colors;
+
+        match mode {
+            Mode::TriangleStrip => {
+                for i in 0..indices.len().saturating_sub(2) {
+                    if i % 2 == 0 {
+                        mesh.add_triangle([indices[i], indices[i + 1], indices[i + 2]], 0);
+                    } else {
+                        mesh.add_triangle([indices[i + 1], indices[i], indices[i + 2]], 0);
+                    }
+                }
+            }
+            Mode::TriangleFan => {
+                for i in 1..indices.len().saturating_sub(1) {
+                    mesh.add_triangle([indices[0], indices[i], indices[i + 1]], 0);
+                }
+            }
+            _ => {
+                for triangle in indices.chunks_exact(3) {
+                    mesh.add_triangle([triangle[0], triangle[1], triangle[2]], 0);
+                }
+            }
+        }
+
+        Ok(Some(mesh))
+    }
+
+    // Maps a metallic-roughness material onto the closest of the existing materials
+    fn material(&mut self, material: &::gltf::Material) -> io::Result<Arc<Material>> {
+        if let Some(material) = self.materials.get(&material.index()) {
+            return Ok(Arc::clone(material));
+        }
+
+        let strength = f64::from(material.emissive_strength().unwrap_or(1.0));
+        let emissive = to_vector(material.emissive_factor()) * strength;
+        let pbr = material.pbr_metallic_roughness();
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |transmission| transmission.transmission_factor());
+
+        let result = if emissive.x.max(emissive.y).max(emissive.z) > 0.0 {
+            Material::DiffuseLight(match material.emissive_texture() {
+                Some(info) => {
+                    let texture = self.texture(info.texture().source().index(), emissive)?;
+                    DiffuseLight::new(texture)
+                }
+                None => DiffuseLight::from_vec3(emissive),
+            })
+        } else if transmission > 0.5 {
+            let ior = material.ior().map_or(1.5, f64::from);
+            Material::Dielectric(Dielectric::from(ior))
+        } else {
+            let [r, g, b, _] = pbr.base_color_factor();
+            let base_color = to_vector([r, g, b]);
+            let albedo = match pbr.base_color_texture() {
+                Some(info) => self.texture(info.texture().source().index(), base_color)?,
+                None => Texture::ConstantTexture(ConstantTexture::new(base_color)),
+            };
+
+            // The texture holds linear roughness in green and metalness in blue
+            let mut metallic = f64::from(pbr.metallic_factor());
+            let roughness = f64::from(pbr.roughness_factor());
+            let roughness = match pbr.metallic_roughness_texture() {
+                Some(info) => {
+                    let image = self.image(info.texture().source().index())?;
+                    // A surface is either a metal or not here, so metalness is averaged
+                    let (mean, uniform) = mean_channel(&image, 2);
+                    if !uniform {
+                        self.scene.warnings.push(format!(
+                            "material {} averages the metalness of its texture",
+                            material
+                                .index()
+                                .map_or("default".to_string(), |i| i.to_string())
+                        ));
+                    }
+                    metallic *= mean;
+                    Texture::ImageTexture(ImageTexture::linear(Arc::new(gray_channel(
+                        &image, 1, roughness,
+                    ))))
+                }
+                None => {
+                    Texture::ConstantTexture(ConstantTexture::from(roughness, roughness, roughness))
+                }
+            };
+
+            if metallic >= 0.5 {
+                Material::Conductor(Conductor::reflectance(albedo, roughness))
+            } else {
+                Material::Lambertian(Lambertian::new(albedo))
+            }
+        };
+
+        let result = Arc::new(result);
+        self.materials.insert(material.index(), Arc::clone(&result));
+        Ok(result)
+    }
+
+    // Color textures are sRGB encoded and scaled by the matching factor of the material
+    fn texture(&mut self, image: usize, factor: Vector3<f64>) -> io::Result<Texture> {
+        let image = self.image(image)?;
+        Ok(Texture::ImageTexture(ImageTexture::tinted(image, factor)))
+    }
+
+    fn image(&mut self, index: usize) -> io::Result<Arc<RgbImage>> {
+        if let Some(image) = self.textures.get(&index) {
+            return Ok(Arc::clone(image));
+        }
+
+        let image = Arc::new(to_rgb_image(&self.images[index])?);
+        self.textures.insert(index, Arc::clone(&image));
+        Ok(image)
+    }
+}
+
+fn to_rgb_image(data: &::gltf::image::Data) -> io::Result<RgbImage> {
+    let (channels, size) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+
+    let channel = |bytes: &[u8]| -> u8 {
+        match size {
+            1 => bytes[0],
+            2 => (u16::from_ne_bytes([bytes[0], bytes[1]]) >> 8) as u8,
+            _ => {
+                let value = f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                (value.clamp(0.0, 1.0) * 255.0) as u8
+            }
+        }
+    };
+
+    let mut rgb = Vec::with_capacity(data.width as usize * data.height as usize * 3);
+    for pixel in data.pixels.chunks_exact(channels * size) {
+        let r = channel(&pixel[..size]);
+        match channels {
+            1 | 2 => rgb.extend_from_slice(&[r, r, r]),
+            _ => rgb.extend_from_slice(&[
+                r,
+                channel(&pixel[size..2 * size]),
+                channel(&pixel[2 * size..3 * size]),
+            ]),
+        }
+    }
+
+    RgbImage::from_raw(data.width, data.height, rgb)
+        .ok_or_else(|| invalid_data("glTF image data is truncated"))
+}
+
+// Returns the average of one channel and whether every pixel has that value
+fn mean_channel(image: &RgbImage, channel: usize) -> (f64, bool) {
+    let first = image.pixels().next().map(|pixel| pixel[channel]);
+    let mut sum = 0.0;
+    let mut uniform = true;
+    for pixel in image.pixels() {
+        uniform &= Some(pixel[channel]) == first;
+        sum += f64::from(pixel[channel]);
+    }
+
+    let count = f64::from(image.width()) * f64::from(image.height());
+    if count > 0.0 {
+        (sum / (255.0 * count), uniform)
+    } else {
+        (1.0, true)
+    }
+}
+
+// One channel of an image scaled by `factor`, copied to all three channels
+fn gray_channel(image: &RgbImage, channel: usize, factor: f64) -> RgbImage {
+    let mut gray = RgbImage::new(image.width(), image.height());
+    for (to, from) in gray.pixels_mut().zip(image.pixels()) {
+        let value = (f64::from(from[channel]) * factor)
+            .round()
+            .clamp(0.0, 255.0) as u8;
+        *to = Rgb([value; 3]);
+    }
+
+    gray
+}
+
+fn to_point(p: [f32; 3]) -> Point3<f64> {
+    Point3::new(f64::from(p[0]), f64::from(p[1]), f64::from(p[2]))
+}
+
+fn to_vector(v: [f32; 3]) -> Vector3<f64> {
+    vec3(f64::from(v[0]), f64::from(v[1]), f64::from(v[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::Hittable;
+    use crate::ray::Ray;
+
+    // A gold-colored metal triangle under a node rotated a quarter turn about y, next to a camera node turned the
+    // same way, both under a node that scales by 2 and moves to (1, 2, 3)
+    const SCENE: &str = r#"{
+        "asset": { "version": "2.0" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [
+            { "translation": [1, 2, 3], "scale": [2, 2, 2], "children": [1, 2] },
+            { "rotation": [0, 0.70710678, 0, 0.70710678], "mesh": 0 },
+            {
+                "translation": [0, 0, 5],
+                "rotation": [0, 0.70710678, 0, 0.70710678],
+                "camera": 0
+            }
+        ],
+        "cameras": [
+            { "type": "perspective", "perspective": { "yfov": 0.8, "znear": 0.1 } }
+        ],
+        "meshes": [{ "primitives": [{ "attributes": { "POSITION": 0 }, "material": 0 }] }],
+        "materials": [{
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [0.9, 0.6, 0.2, 1],
+                "metallicFactor": 1,
+                "roughnessFactor": 0.3
+            }
+        }],
+        "accessors": [{
+            "bufferView": 0,
+            "componentType": 5126,
+            "count": 3,
+            "type": "VEC3",
+            "min": [0, 0, 0],
+            "max": [1, 1, 0]
+        }],
+        "bufferViews": [{ "buffer": 0, "byteLength": 36 }],
+        "buffers": [{
+            "byteLength": 36,
+            "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"
+        }]
+    }"#;
+
+    fn load_str(json: &str, aspect: f64) -> GltfScene {
+        let (document, buffers, images) = ::gltf::import_slice(json.as_bytes()).unwrap();
+        let scene = document.default_scene().unwrap();
+        load_scene(&scene, buffers, images, aspect).unwrap()
+    }
+
+    #[test]
+    fn composes_node_transforms() {
+        let scene = load_str(SCENE, 2.0);
+        assert_eq!(scene.objects.size(), 1);
+        assert!(scene.warnings.is_empty());
+
+        // (0, 0, 0), (1, 0, 0) and (0, 1, 0) end up at (1, 2, 3), (1, 2, 1) and (1, 4, 3)
+        let bx = scene.objects.bounding_box(0.0, 0.0).unwrap();
+        assert!((bx.min - vec3(1.0, 2.0, 1.0)).magnitude() < 1e-3);
+        assert!((bx.max - vec3(1.0, 4.0, 3.0)).magnitude() < 1e-3);
+
+        let ray = Ray::from(Point3::new(5.0, 2.5, 2.5), vec3(-1.0, 0.0, 0.0), 0.0);
+        let hit = scene.objects.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-6);
+        assert!(matches!(*hit.material, Material::Conductor(_)));
+    }
+
+    #[test]
+    fn maps_cameras() {
+        let scene = load_str(SCENE, 2.0);
+        assert_eq!(scene.cameras.len(), 1);
+        let camera = &scene.cameras[0];
+
+        // Looking down -x from (1, 2, 13), with y up
+        assert!((camera.origin - Point3::new(1.0, 2.0, 13.0)).magnitude() < 1e-6);
+        assert!((camera.w - vec3(1.0, 0.0, 0.0)).magnitude() < 1e-6);
+        assert!((camera.v - vec3(0.0, 1.0, 0.0)).magnitude() < 1e-6);
+        let height = 2.0 * 0.4f64.tan();
+        assert!((camera.vertical.magnitude() - height).abs() < 1e-6);
+        assert!((camera.horizontal.magnitude() - 2.0 * height).abs() < 1e-6);
+    }
+}
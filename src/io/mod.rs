@@ -1 +1,15 @@
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+pub mod stl;
 pub mod write;
+
+use std::error::Error;
+use std::io;
+
+fn invalid_data<E>(error: E) -> io::Error
+where
+    E: Into<Box<dyn Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
@@ -0,0 +1,561 @@
+use super::invalid_data;
+use crate::materials::dielectric::Dielectric;
+use crate::materials::lambertian::Lambertian;
+use crate::materials::light::DiffuseLight;
+use crate::materials::metal::Metal;
+use crate::materials::Material;
+use crate::objects::mesh::TriangleMesh;
+use crate::objects::subdivision::PolygonMesh;
+use crate::objects::subdivision::SubdivisionScheme;
+use crate::textures::image_texture::ImageTexture;
+use crate::textures::Texture;
+
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::str::SplitWhitespace;
+use std::sync::Arc;
+
+// Refer: http://paulbourke.net/dataformats/obj/ and http://paulbourke.net/dataformats/mtl/
+pub fn load<P: AsRef<Path>>(path: P) -> io::Result<TriangleMesh> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let reader = BufReader::new(File::open(path)?);
+    read_mesh(reader, path, |name| load_mtl(dir.join(name)))
+}
+
+// Builds the mesh of an OBJ file from `reader`, where `mtllib` loads the material libraries
+// it names. `path` is only used in error messages.
+fn read_mesh<R, M>(reader: R, path: &Path, mtllib: M) -> io::Result<TriangleMesh>
+where
+    R: BufRead,
+    M: FnMut(&str) -> io::Result<Vec<(String, Material)>>,
+{
+    let mut mesh = TriangleMesh::new(default_material());
+    let mut vertices: HashMap<VertexKey, u32> = HashMap::new();
+    let mut mesh_normals: Vec<Vector3<f64>> = Vec::new();
+    let mut mesh_uvs: Vec<(f64, f64)> = Vec::new();
+    let mut all_normals = true;
+    let mut all_uvs = true;
+    let mut face: Vec<u32> = Vec::new();
+
+    // Faces are added to the mesh as they are read, so only the vertex data is kept around
+    let obj = read(reader, path, mtllib, |obj, keys, material| {
+        face.clear();
+        for key in keys {
+            let index = match vertices.get(key) {
+                Some(&index) => index,
+                None => {
+                    let index = mesh.positions.len() as u32;
+                    mesh.positions.push(obj.positions[key.position]);
+                    match key.normal {
+                        Some(n) => mesh_normals.push(obj.normals[n]),
+                        None => {
+                            all_normals = false;
+                            mesh_normals.push(vec3(0.0, 0.0, 0.0));
+                        }
+                    }
+                    match key.uv {
+                        Some(t) => mesh_uvs.push(obj.uvs[t]),
+                        None => {
+                            all_uvs = false;
+                            mesh_uvs.push((0.0, 0.0));
+                        }
+                    }
+                    vertices.insert(*key, index);
+                    index
+                }
+            };
+            face.push(index);
+        }
+
+        for i in 1..face.len() - 1 {
+            mesh.add_triangle([face[0], face[i], face[i + 1]], material);
+        }
+    })?;
+
+    mesh.materials = obj.materials;
+    if all_normals && !mesh.positions.is_empty() {
+        mesh.normals = Some(mesh_normals);
+    }
+    if all_uvs && !mesh.positions.is_empty() {
+        mesh.uvs = Some(mesh_uvs);
+    }
+
+    Ok(mesh)
+}
+
+// Loads the faces as the control cage of a subdivision surface and refines it `levels` times.
+// Edges are made sharp with the `t crease` tags written by OpenSubdiv. Normals in the file are
+// ignored since the refined surface has its own.
+pub fn load_subdivided<P: AsRef<Path>>(
+    path: P,
+    scheme: SubdivisionScheme,
+    levels: u32,
+) -> io::Result<TriangleMesh> {
+    let path = path.as_ref();
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let reader = BufReader::new(File::open(path)?);
+    let mtllib = |name: &str| load_mtl(dir.join(name));
+
+    let mut cage = PolygonMesh::new(default_material());
+    let mut all_uvs = true;
+    let obj = read(reader, path, mtllib, |_, keys, material| {
+        cage.add_face(
+            keys.iter().map(|key| key.position as u32).collect(),
+            material,
+        );
+        if all_uvs && keys.iter().all(|key| key.uv.is_some()) {
+            let uv_face = keys.iter().map(|key| key.uv.unwrap() as u32).collect();
+            cage.uv_faces.push(uv_face);
+        } else {
+            all_uvs = false;
+        }
+    })?;
+
+    cage.materials = obj.materials;
+    cage.positions = obj.positions;
+    if all_uvs && !cage.is_empty() {
+        cage.uvs = Some(obj.uvs);
+    } else {
+        cage.uv_faces.clear();
+    }
+    for (a, b, sharpness) in obj.creases {
+        cage.set_crease(a, b, sharpness);
+    }
+
+    Ok(cage.subdivide(scheme, levels).to_triangle_mesh())
+}
+
+// Everything in an OBJ file except its faces, which are passed on one at a time
+struct ObjData {
+    positions: Vec<Point3<f64>>,
+    normals: Vec<Vector3<f64>>,
+    uvs: Vec<(f64, f64)>,
+    // The first material is used by faces before any `usemtl`
+    materials: Vec<Arc<Material>>,
+    creases: Vec<(u32, u32, f64)>,
+}
+
+// Reads an OBJ file and calls `add_face` with the vertices and material of every face
+fn read<R, M, F>(reader: R, path: &Path, mut mtllib: M, mut add_face: F) -> io::Result<ObjData>
+where
+    R: BufRead,
+    M: FnMut(&str) -> io::Result<Vec<(String, Material)>>,
+    F: FnMut(&ObjData, &[VertexKey], u32),
+{
+    let mut obj = ObjData {
+        positions: Vec::new(),
+        normals: Vec::new(),
+        uvs: Vec::new(),
+        materials: vec![default_material()],
+        creases: Vec::new(),
+    };
+
+    let mut material_ids: HashMap<String, u32> = HashMap::new();
+    let mut current_material = 0;
+    let mut face: Vec<VertexKey> = Vec::new();
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let error =
+            |message: &str| invalid_data(format!("{}:{}: {}", path.display(), number + 1, message));
+        let mut tokens = line.split_whitespace();
+
+        match tokens.next() {
+            Some("v") => {
+                let p = parse_floats::<3>(&mut tokens).ok_or_else(|| error("invalid vertex"))?;
+                obj.positions.push(Point3::new(p[0], p[1], p[2]));
+            }
+            Some("vn") => {
+                let n = parse_floats::<3>(&mut tokens).ok_or_else(|| error("invalid normal"))?;
+                obj.normals.push(vec3(n[0], n[1], n[2]));
+            }
+            Some("vt") => {
+                let u = parse(tokens.next()).ok_or_else(|| error("invalid texture coordinate"))?;
+                let v = match tokens.next() {
+                    Some(token) => token
+                        .parse()
+                        .map_err(|_| error("invalid texture coordinate"))?,
+                    None => 0.0,
+                };
+                obj.uvs.push((u, v));
+            }
+            Some("f") => {
+                face.clear();
+                for token in tokens {
+                    let key = VertexKey::parse(
+                        token,
+                        obj.positions.len(),
+                        obj.uvs.len(),
+                        obj.normals.len(),
+                    )
+                    .ok_or_else(|| error("invalid face vertex"))?;
+                    face.push(key);
+                }
+                if face.len() < 3 {
+                    return Err(error("face with less than three vertices"));
+                }
+                add_face(&obj, &face, current_material);
+            }
+            // OpenSubdiv tags edge chains as `t crease 2/1/0 v0 v1 sharpness`, counting vertices
+            // from 0. The counts are of the vertices, sharpness values and strings that follow.
+            Some("t") if tokens.next() == Some("crease") => {
+                let counts: Vec<usize> = tokens
+                    .next()
+                    .and_then(|token| token.split('/').map(|n| n.parse().ok()).collect())
+                    .ok_or_else(|| error("invalid crease tag"))?;
+                let (vertex_count, sharpness_count) = match counts[..] {
+                    [v, s, ..] if v >= 2 && s >= 1 => (v, s),
+                    _ => return Err(error("invalid crease tag")),
+                };
+
+                let mut vertices = Vec::with_capacity(vertex_count);
+                for _ in 0..vertex_count {
+                    let v: u32 = parse(tokens.next()).ok_or_else(|| error("invalid crease"))?;
+                    if v as usize >= obj.positions.len() {
+                        return Err(error("invalid crease"));
+                    }
+                    vertices.push(v);
+                }
+                let mut sharpness = Vec::with_capacity(sharpness_count);
+                for _ in 0..sharpness_count {
+                    sharpness.push(parse(tokens.next()).ok_or_else(|| error("invalid crease"))?);
+                }
+
+                // A single sharpness applies to the whole chain, otherwise there is one per edge
+                for i in 0..vertex_count - 1 {
+                    let s = sharpness[i.min(sharpness_count - 1)];
+                    obj.creases.push((vertices[i], vertices[i + 1], s));
+                }
+            }
+            Some("mtllib") => {
+                for name in tokens {
+                    for (name, material) in mtllib(name)? {
+                        material_ids.insert(name, obj.materials.len() as u32);
+                        obj.materials.push(Arc::new(material));
+                    }
+                }
+            }
+            Some("usemtl") => {
+                let name = tokens.collect::<Vec<_>>().join(" ");
+                current_material = *material_ids
+                    .get(&name)
+                    .ok_or_else(|| error(&format!("unknown material {}", name)))?;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(obj)
+}
+
+fn default_material() -> Arc<Material> {
+    Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+}
+
+pub fn load_mtl<P: AsRef<Path>>(path: P) -> io::Result<Vec<(String, Material)>> {
+    let path = path.as_ref();
+    read_mtl(BufReader::new(File::open(path)?), path)
+}
+
+// Texture maps are found relative to `path`
+fn read_mtl<R: BufRead>(reader: R, path: &Path) -> io::Result<Vec<(String, Material)>> {
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+    let mut materials: Vec<(String, MtlMaterial)> = Vec::new();
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let error =
+            |message: &str| invalid_data(format!("{}:{}: {}", path.display(), number + 1, message));
+        let mut tokens = line.split_whitespace();
+
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<_>>().join(" ");
+            materials.push((name, MtlMaterial::default()));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some((_, material)) => material,
+            None => continue,
+        };
+        match keyword {
+            "Kd" | "Ks" | "Ke" => {
+                let c = parse_floats::<3>(&mut tokens).ok_or_else(|| error("invalid color"))?;
+                let color = vec3(c[0], c[1], c[2]);
+                match keyword {
+                    "Kd" => material.diffuse = color,
+                    "Ks" => material.specular = color,
+                    _ => material.emission = color,
+                }
+            }
+            "Ns" => material.shininess = parse(tokens.next()).ok_or_else(|| error("invalid Ns"))?,
+            "Ni" => material.ior = Some(parse(tokens.next()).ok_or_else(|| error("invalid Ni"))?),
+            "d" => material.dissolve = parse(tokens.next()).ok_or_else(|| error("invalid d"))?,
+            "Tr" => {
+                let transparency: f64 = parse(tokens.next()).ok_or_else(|| error("invalid Tr"))?;
+                material.dissolve = 1.0 - transparency;
+            }
+            "illum" => {
+                material.illum = parse(tokens.next()).ok_or_else(|| error("invalid illum"))?
+            }
+            "map_Kd" => {
+                // Texture options come before the file name, which is always the last token
+                let name = tokens.last().ok_or_else(|| error("missing map_Kd file"))?;
+                material.diffuse_map = Some(dir.join(name));
+            }
+            _ => {}
+        }
+    }
+
+    materials
+        .into_iter()
+        .map(|(name, material)| Ok((name, material.to_material()?)))
+        .collect()
+}
+
+struct MtlMaterial {
+    diffuse: Vector3<f64>,
+    specular: Vector3<f64>,
+    emission: Vector3<f64>,
+    shininess: f64,
+    ior: Option<f64>,
+    dissolve: f64,
+    illum: u32,
+    diffuse_map: Option<PathBuf>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: vec3(0.8, 0.8, 0.8),
+            specular: vec3(0.0, 0.0, 0.0),
+            emission: vec3(0.0, 0.0, 0.0),
+            shininess: 0.0,
+            ior: None,
+            dissolve: 1.0,
+            illum: 2,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn to_material(&self) -> io::Result<Material> {
+        if max_component(self.emission) > 0.0 {
+            return Ok(Material::DiffuseLight(DiffuseLight::from_vec3(
+                self.emission,
+            )));
+        }
+
+        let transparent = self.dissolve < 1.0;
+        if transparent || [4, 6, 7, 9].contains(&self.illum) {
+            let ior = self.ior.filter(|&ior| ior > 1.0).unwrap_or(1.5);
+            return Ok(Material::Dielectric(Dielectric::from(ior)));
+        }
+
+        let texture = match self.diffuse_map {
+            Some(ref path) => Some(ImageTexture::open(path).map_err(|e| {
+                invalid_data(format!("{}: failed to load texture: {}", path.display(), e))
+            })?),
+            None => None,
+        };
+
+        // Plastics also have a specular color, so only mirrors or materials without a diffuse
+        // color are metals
+        let black = texture.is_none() && max_component(self.diffuse) <= 0.0;
+        if self.illum == 3 || (black && max_component(self.specular) > 0.0) {
+            let fuzz = (2.0 / (self.shininess + 2.0)).sqrt();
+            return Ok(Material::Metal(match texture {
+                Some(texture) => Metal::textured(Texture::ImageTexture(texture), fuzz),
+                None => Metal::new(self.specular, fuzz),
+            }));
+        }
+
+        match texture {
+            Some(texture) => Ok(Material::Lambertian(Lambertian::new(
+                Texture::ImageTexture(texture),
+            ))),
+            None => Ok(Material::Lambertian(Lambertian::from_vec3(self.diffuse))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct VertexKey {
+    position: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
+}
+
+impl VertexKey {
+    fn parse(token: &str, positions: usize, uvs: usize, normals: usize) -> Option<VertexKey> {
+        let mut parts = token.split('/');
+        let position = resolve_index(parts.next()?, positions)?;
+        let uv = match parts.next() {
+            Some("") | None => None,
+            Some(part) => Some(resolve_index(part, uvs)?),
+        };
+        let normal = match parts.next() {
+            Some("") | None => None,
+            Some(part) => Some(resolve_index(part, normals)?),
+        };
+
+        Some(VertexKey {
+            position,
+            uv,
+            normal,
+        })
+    }
+}
+
+// OBJ indices are 1-based, negative indices are relative to the end of the list
+fn resolve_index(token: &str, len: usize) -> Option<usize> {
+    let index: i64 = token.parse().ok()?;
+    let index = if index < 0 {
+        len as i64 + index
+    } else {
+        index - 1
+    };
+
+    if index >= 0 && (index as usize) < len {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+fn parse<T: FromStr>(token: Option<&str>) -> Option<T> {
+    token?.parse().ok()
+}
+
+fn parse_floats<const N: usize>(tokens: &mut SplitWhitespace) -> Option<[f64; N]> {
+    let mut values = [0.0; N];
+    for value in values.iter_mut() {
+        *value = parse(tokens.next())?;
+    }
+
+    Some(values)
+}
+
+fn max_component(v: Vector3<f64>) -> f64 {
+    v.x.max(v.y).max(v.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::Cursor;
+
+    const MTL: &str = "
+newmtl red
+Kd 0.8 0.2 0.2
+
+newmtl plastic
+Kd 0.8 0.2 0.2
+Ks 1 1 1
+Ns 100
+
+newmtl mirror
+Kd 0.8 0.2 0.2
+Ks 0.9 0.9 0.9
+illum 3
+";
+
+    fn load_str(obj: &str) -> io::Result<TriangleMesh> {
+        read_mesh(Cursor::new(obj), Path::new("test.obj"), |name| {
+            assert_eq!(name, "test.mtl");
+            read_mtl(Cursor::new(MTL), Path::new(name))
+        })
+    }
+
+    #[test]
+    fn reads_faces_with_uvs_and_normals() {
+        let mesh = load_str(
+            "mtllib test.mtl
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+v 0.5 1.5 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+usemtl red
+f 1/1/1 2/2/1 3/3/1 5/3/1 4/4/1
+usemtl plastic
+# Counted back from the last vertex, uv and normal read so far
+f -5/-4/-1 -4/-3/-1 -1/-2/-1
+",
+        )
+        .unwrap();
+
+        assert_eq!(mesh.positions.len(), 5);
+        assert_eq!(
+            mesh.indices,
+            vec![[0, 1, 2], [0, 2, 3], [0, 3, 4], [0, 1, 3]]
+        );
+        assert_eq!(mesh.face_materials, vec![1, 1, 1, 2]);
+        assert_eq!(mesh.positions[3], Point3::new(0.5, 1.5, 0.0));
+        assert_eq!(mesh.uvs.as_ref().unwrap()[3], (1.0, 1.0));
+        assert!(mesh
+            .normals
+            .unwrap()
+            .iter()
+            .all(|&n| n == vec3(0.0, 0.0, 1.0)));
+
+        assert_eq!(mesh.materials.len(), 4);
+        assert!(matches!(*mesh.materials[1], Material::Lambertian(_)));
+        assert!(matches!(*mesh.materials[2], Material::Lambertian(_)));
+        assert!(matches!(*mesh.materials[3], Material::Metal(_)));
+    }
+
+    #[test]
+    fn reads_faces_without_attributes() {
+        let mesh = load_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nf 3//1 2//1 1//1\n");
+        assert!(mesh.is_err());
+
+        let mesh = load_str("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\n").unwrap();
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+        assert_eq!(mesh.face_materials, vec![0]);
+        assert!(mesh.normals.is_none() && mesh.uvs.is_none());
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let error = match load_str("mtllib test.mtl\nv 0 0 0\nusemtl gold\n") {
+            Ok(_) => panic!("loaded an unknown material"),
+            Err(error) => error,
+        };
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert!(error.to_string().contains("unknown material gold"));
+
+        for obj in &[
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 -4\n",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2\n",
+            "v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n",
+            "v 0 0 x\n",
+        ] {
+            assert!(load_str(obj).is_err(), "{:?}", obj);
+        }
+    }
+}
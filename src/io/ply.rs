@@ -0,0 +1,604 @@
+use super::invalid_data;
+use crate::materials::Material;
+use crate::objects::mesh::TriangleMesh;
+
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::Arc;
+
+// Space reserved up front for an element is capped, so a header with a bogus count fails at the
+// end of the data instead of allocating all of it
+const MAX_RESERVE: usize = 1 << 20;
+
+// Refer: http://paulbourke.net/dataformats/ply/
+pub fn load<P: AsRef<Path>>(path: P, material: Arc<Material>) -> io::Result<TriangleMesh> {
+    let path = path.as_ref();
+    read(BufReader::new(File::open(path)?), path, material)
+}
+
+// `path` is only used in error messages
+fn read<R: BufRead>(
+    mut reader: R,
+    path: &Path,
+    material: Arc<Material>,
+) -> io::Result<TriangleMesh> {
+    let header = Header::read(&mut reader)?;
+    let mut records = RecordReader::new(reader, header.format);
+
+    let mut mesh = TriangleMesh::new(material);
+    let mut normals: Vec<Vector3<f64>> = Vec::new();
+    let mut uvs: Vec<(f64, f64)> = Vec::new();
+    let mut colors: Vec<Vector3<f64>> = Vec::new();
+    let mut face: Vec<u32> = Vec::new();
+
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => {
+                let layout = VertexLayout::new(element);
+                mesh.positions.reserve(element.count.min(MAX_RESERVE));
+                let mut values = vec![0.0; element.properties.len()];
+                for _ in 0..element.count {
+                    records.start_record()?;
+                    for (value, property) in values.iter_mut().zip(&element.properties) {
+                        *value = match property.kind {
+                            PropertyKind::Scalar(ty) => records.read(ty)?,
+                            PropertyKind::List(count_ty, ty) => {
+                                records.skip_list(count_ty, ty)?;
+                                0.0
+                            }
+                        };
+                    }
+                    layout.push(&values, &mut mesh, &mut normals, &mut uvs, &mut colors);
+                }
+            }
+            "face" => {
+                let indices = element
+                    .properties
+                    .iter()
+                    .position(|p| p.name == "vertex_indices" || p.name == "vertex_index")
+                    .ok_or_else(|| invalid_data("PLY face element has no vertex_indices"))?;
+                if let PropertyKind::Scalar(_) = element.properties[indices].kind {
+                    return Err(invalid_data("PLY vertex_indices is not a list"));
+                }
+                mesh.indices.reserve(element.count.min(MAX_RESERVE));
+                mesh.face_materials.reserve(element.count.min(MAX_RESERVE));
+                for _ in 0..element.count {
+                    records.start_record()?;
+                    for (i, property) in element.properties.iter().enumerate() {
+                        match property.kind {
+                            PropertyKind::List(count_ty, ty) if i == indices => {
+                                let count = records.read_index(count_ty)?;
+                                face.clear();
+                                for _ in 0..count {
+                                    face.push(records.read_index(ty)?);
+                                }
+                            }
+                            PropertyKind::List(count_ty, ty) => records.skip_list(count_ty, ty)?,
+                            PropertyKind::Scalar(ty) => {
+                                records.read(ty)?;
+                            }
+                        }
+                    }
+                    for i in 1..face.len().saturating_sub(1) {
+                        mesh.add_triangle([face[0], face[i], face[i + 1]], 0);
+                    }
+                }
+            }
+            _ => {
+                for _ in 0..element.count {
+                    records.start_record()?;
+                    for property in &element.properties {
+                        match property.kind {
+                            PropertyKind::Scalar(ty) => {
+                                records.read(ty)?;
+                            }
+                            PropertyKind::List(count_ty, ty) => records.skip_list(count_ty, ty)?,
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Faces may come before the vertices they use, so indices are only checked at the end
+    if let Some(&index) = mesh
+        .indices
+        .iter()
+        .flatten()
+        .find(|&&index| index as usize >= mesh.positions.len())
+    {
+        return Err(invalid_data(format!(
+            "{}: vertex index {} out of range",
+            path.display(),
+            index
+        )));
+    }
+
+    let vertex = header.elements.iter().find(|e| e.name == "vertex");
+    if let Some(layout) = vertex.map(VertexLayout::new) {
+        if layout.normal.is_some() {
+            mesh.normals = Some(normals);
+        }
+        if layout.uv.is_some() {
+            mesh.uvs = Some(uvs);
+        }
+        if layout.color.is_some() {
+            mesh.colors = Some(colors);
+        }
+    }
+
+    Ok(mesh)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl ScalarType {
+    fn parse(name: &str) -> Option<ScalarType> {
+        match name {
+            "char" | "int8" => Some(ScalarType::Int8),
+            "uchar" | "uint8" => Some(ScalarType::UInt8),
+            "short" | "int16" => Some(ScalarType::Int16),
+            "ushort" | "uint16" => Some(ScalarType::UInt16),
+            "int" | "int32" => Some(ScalarType::Int32),
+            "uint" | "uint32" => Some(ScalarType::UInt32),
+            "float" | "float32" => Some(ScalarType::Float32),
+            "double" | "float64" => Some(ScalarType::Float64),
+            _ => None,
+        }
+    }
+
+    fn size(self) -> usize {
+        match self {
+            ScalarType::Int8 | ScalarType::UInt8 => 1,
+            ScalarType::Int16 | ScalarType::UInt16 => 2,
+            ScalarType::Int32 | ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::Float64 => 8,
+        }
+    }
+
+    // Scale factor that maps integer color channels onto [0, 1]
+    fn normalization(self) -> f64 {
+        match self {
+            ScalarType::UInt8 => 255.0,
+            ScalarType::UInt16 => 65535.0,
+            _ => 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum PropertyKind {
+    Scalar(ScalarType),
+    List(ScalarType, ScalarType),
+}
+
+struct Property {
+    name: String,
+    kind: PropertyKind,
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+impl Header {
+    fn read<R: BufRead>(reader: &mut R) -> io::Result<Header> {
+        let mut line = String::new();
+        let mut next_line = |line: &mut String| -> io::Result<()> {
+            line.clear();
+            if reader.read_line(line)? == 0 {
+                return Err(invalid_data("unexpected end of PLY header"));
+            }
+            Ok(())
+        };
+
+        next_line(&mut line)?;
+        if line.trim_end() != "ply" {
+            return Err(invalid_data("not a PLY file"));
+        }
+
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+        loop {
+            next_line(&mut line)?;
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let error = || invalid_data(format!("invalid PLY header line: {}", line.trim_end()));
+
+            match tokens.first().cloned() {
+                Some("format") => {
+                    format = match tokens.get(1).cloned() {
+                        Some("ascii") => Some(Format::Ascii),
+                        Some("binary_little_endian") => Some(Format::BinaryLittleEndian),
+                        Some("binary_big_endian") => Some(Format::BinaryBigEndian),
+                        _ => return Err(error()),
+                    }
+                }
+                Some("element") => {
+                    if tokens.len() != 3 {
+                        return Err(error());
+                    }
+                    elements.push(Element {
+                        name: tokens[1].to_string(),
+                        count: tokens[2].parse().map_err(|_| error())?,
+                        properties: Vec::new(),
+                    });
+                }
+                Some("property") => {
+                    let kind = match tokens.len() {
+                        3 => PropertyKind::Scalar(ScalarType::parse(tokens[1]).ok_or_else(error)?),
+                        5 if tokens[1] == "list" => PropertyKind::List(
+                            ScalarType::parse(tokens[2]).ok_or_else(error)?,
+                            ScalarType::parse(tokens[3]).ok_or_else(error)?,
+                        ),
+                        _ => return Err(error()),
+                    };
+                    let element = elements.last_mut().ok_or_else(error)?;
+                    element.properties.push(Property {
+                        name: tokens[tokens.len() - 1].to_string(),
+                        kind,
+                    });
+                }
+                Some("end_header") => break,
+                Some("comment") | Some("obj_info") | None => {}
+                Some(_) => return Err(error()),
+            }
+        }
+
+        match format {
+            Some(format) => Ok(Header { format, elements }),
+            None => Err(invalid_data("PLY header has no format line")),
+        }
+    }
+}
+
+struct VertexLayout {
+    position: [Option<(usize, f64)>; 3],
+    normal: Option<[(usize, f64); 3]>,
+    uv: Option<[(usize, f64); 2]>,
+    color: Option<[(usize, f64); 3]>,
+}
+
+impl VertexLayout {
+    fn new(element: &Element) -> VertexLayout {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| names.contains(&p.name.as_str()))
+                .map(|i| match element.properties[i].kind {
+                    PropertyKind::Scalar(ty) => (i, ty.normalization()),
+                    PropertyKind::List(..) => (i, 1.0),
+                })
+        };
+        let position = [find(&["x"]), find(&["y"]), find(&["z"])];
+        let normal = [find(&["nx"]), find(&["ny"]), find(&["nz"])];
+        let uv = [
+            find(&["u", "s", "texture_u", "texture_s"]),
+            find(&["v", "t", "texture_v", "texture_t"]),
+        ];
+        let color = [
+            find(&["red", "r", "diffuse_red"]),
+            find(&["green", "g", "diffuse_green"]),
+            find(&["blue", "b", "diffuse_blue"]),
+        ];
+
+        VertexLayout {
+            position,
+            normal: match normal {
+                [Some(x), Some(y), Some(z)] => Some([x, y, z]),
+                _ => None,
+            },
+            uv: match uv {
+                [Some(u), Some(v)] => Some([u, v]),
+                _ => None,
+            },
+            color: match color {
+                [Some(r), Some(g), Some(b)] => Some([r, g, b]),
+                _ => None,
+            },
+        }
+    }
+
+    fn push(
+        &self,
+        values: &[f64],
+        mesh: &mut TriangleMesh,
+        normals: &mut Vec<Vector3<f64>>,
+        uvs: &mut Vec<(f64, f64)>,
+        colors: &mut Vec<Vector3<f64>>,
+    ) {
+        let position = |i: usize| self.position[i].map_or(0.0, |(index, _)| values[index]);
+        mesh.positions
+            .push(Point3::new(position(0), position(1), position(2)));
+
+        if let Some([x, y, z]) = self.normal {
+            normals.push(vec3(values[x.0], values[y.0], values[z.0]));
+        }
+        if let Some([u, v]) = self.uv {
+            uvs.push((values[u.0], values[v.0]));
+        }
+        if let Some([r, g, b]) = self.color {
+            colors.push(vec3(
+                values[r.0] / r.1,
+                values[g.0] / g.1,
+                values[b.0] / b.1,
+            ));
+        }
+    }
+}
+
+// Reads the scalars of element records one at a time, so large binary files are streamed
+// instead of being loaded into memory
+struct RecordReader<R: BufRead> {
+    reader: R,
+    format: Format,
+    line: String,
+    position: usize,
+}
+
+impl<R: BufRead> RecordReader<R> {
+    fn new(reader: R, format: Format) -> Self {
+        RecordReader {
+            reader,
+            format,
+            line: String::new(),
+            position: 0,
+        }
+    }
+
+    fn start_record(&mut self) -> io::Result<()> {
+        if self.format == Format::Ascii {
+            loop {
+                self.line.clear();
+                if self.reader.read_line(&mut self.line)? == 0 {
+                    return Err(invalid_data("unexpected end of PLY data"));
+                }
+                if !self.line.trim().is_empty() {
+                    break;
+                }
+            }
+            self.position = 0;
+        }
+
+        Ok(())
+    }
+
+    fn read(&mut self, ty: ScalarType) -> io::Result<f64> {
+        match self.format {
+            Format::Ascii => self.read_ascii(),
+            Format::BinaryLittleEndian => self.read_binary(ty, true),
+            Format::BinaryBigEndian => self.read_binary(ty, false),
+        }
+    }
+
+    // Reads a list count or vertex index, which has to be a whole number that fits a u32
+    fn read_index(&mut self, ty: ScalarType) -> io::Result<u32> {
+        let value = self.read(ty)?;
+        if value < 0.0 || value.fract() != 0.0 || value > f64::from(u32::MAX) {
+            return Err(invalid_data(format!("invalid PLY index: {}", value)));
+        }
+
+        Ok(value as u32)
+    }
+
+    fn skip_list(&mut self, count_ty: ScalarType, ty: ScalarType) -> io::Result<()> {
+        let count = self.read_index(count_ty)?;
+        for _ in 0..count {
+            self.read(ty)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_ascii(&mut self) -> io::Result<f64> {
+        let rest = &self.line[self.position..];
+        let start = rest.len() - rest.trim_start().len();
+        let rest = &rest[start..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..end];
+        self.position += start + end;
+
+        token
+            .parse()
+            .map_err(|_| invalid_data(format!("invalid PLY value: {:?}", token)))
+    }
+
+    fn read_binary(&mut self, ty: ScalarType, little_endian: bool) -> io::Result<f64> {
+        let mut bytes = [0u8; 8];
+        let bytes = &mut bytes[..ty.size()];
+        self.reader.read_exact(bytes)?;
+        if little_endian != cfg!(target_endian = "little") {
+            bytes.reverse();
+        }
+
+        let value = match ty {
+            ScalarType::Int8 => f64::from(bytes[0] as i8),
+            ScalarType::UInt8 => f64::from(bytes[0]),
+            ScalarType::Int16 => f64::from(i16::from_ne_bytes([bytes[0], bytes[1]])),
+            ScalarType::UInt16 => f64::from(u16::from_ne_bytes([bytes[0], bytes[1]])),
+            ScalarType::Int32 => {
+                f64::from(i32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+            ScalarType::UInt32 => {
+                f64::from(u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+            ScalarType::Float32 => {
+                f64::from(f32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+            }
+            ScalarType::Float64 => f64::from_ne_bytes([
+                bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+            ]),
+        };
+
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    use std::io::Cursor;
+
+    fn load_bytes(name: &str, bytes: &[u8]) -> io::Result<TriangleMesh> {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        read(Cursor::new(bytes), Path::new(name), material)
+    }
+
+    const VERTICES: &str = "\
+element vertex 4
+property float x
+property float y
+property float z
+";
+
+    const FACES: &str = "\
+element face 1
+property list uchar int vertex_indices
+";
+
+    const VERTEX_DATA: &str = "0 0 0\n1 0 0\n1 1 0\n0 1 0\n";
+
+    #[test]
+    fn reads_ascii_quad() {
+        let ply = format!(
+            "ply\nformat ascii 1.0\ncomment a quad\n{}{}end_header\n{}4 0 1 2 3\n",
+            VERTICES, FACES, VERTEX_DATA
+        );
+        let mesh = load_bytes("ascii-quad", ply.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.positions[2], Point3::new(1.0, 1.0, 0.0));
+        assert!(mesh.normals.is_none() && mesh.uvs.is_none() && mesh.colors.is_none());
+    }
+
+    #[test]
+    fn reads_faces_before_vertices() {
+        let ply = format!(
+            "ply\nformat ascii 1.0\n{}{}end_header\n4 0 1 2 3\n{}",
+            FACES, VERTICES, VERTEX_DATA
+        );
+        let mesh = load_bytes("faces-first", ply.as_bytes()).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn reads_binary_colors() {
+        let mut ply = b"ply\nformat binary_little_endian 1.0\n\
+element vertex 3\n\
+property double x\nproperty double y\nproperty double z\n\
+property uchar red\nproperty uchar green\nproperty uchar blue\n\
+element face 1\n\
+property list uchar uint vertex_indices\n\
+end_header\n"
+            .to_vec();
+        for (i, p) in [[0.0f64, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .iter()
+            .enumerate()
+        {
+            for x in p {
+                ply.extend_from_slice(&x.to_le_bytes());
+            }
+            ply.extend_from_slice(&[255, 0, 51 * i as u8]);
+        }
+        ply.push(3);
+        for i in 0u32..3 {
+            ply.extend_from_slice(&i.to_le_bytes());
+        }
+
+        let mesh = load_bytes("binary", &ply).unwrap();
+        assert_eq!(mesh.indices, vec![[0, 1, 2]]);
+        assert_eq!(mesh.positions[1], Point3::new(1.0, 0.0, 0.0));
+        let colors = mesh.colors.unwrap();
+        assert_eq!(colors[2], vec3(1.0, 0.0, 0.4));
+    }
+
+    #[test]
+    fn rejects_bad_indices() {
+        for (name, face) in &[
+            ("negative", "3 0 1 -1"),
+            ("fraction", "3 0 1 1.5"),
+            ("out-of-range", "3 0 1 4"),
+            ("negative-count", "-3 0 1 2"),
+        ] {
+            let ply = format!(
+                "ply\nformat ascii 1.0\n{}{}end_header\n{}{}\n",
+                VERTICES, FACES, VERTEX_DATA, face
+            );
+            assert!(load_bytes(name, ply.as_bytes()).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_scalar_vertex_indices() {
+        let ply = format!(
+            "ply\nformat ascii 1.0\n{}element face 2\nproperty int vertex_indices\nend_header\n{}0\n1\n",
+            VERTICES, VERTEX_DATA
+        );
+        assert!(load_bytes("scalar-indices", ply.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        let cases = [
+            ("not-ply", "plx\nformat ascii 1.0\nend_header\n".to_string()),
+            (
+                "no-format",
+                format!("ply\n{}end_header\n{}", VERTICES, VERTEX_DATA),
+            ),
+            (
+                "bad-format",
+                "ply\nformat binary_middle_endian 1.0\nend_header\n".to_string(),
+            ),
+            (
+                "orphan-property",
+                "ply\nformat ascii 1.0\nproperty float x\nend_header\n".to_string(),
+            ),
+            ("no-end", format!("ply\nformat ascii 1.0\n{}", VERTICES)),
+        ];
+        for (name, ply) in &cases {
+            assert!(load_bytes(name, ply.as_bytes()).is_err(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn rejects_counts_beyond_the_data() {
+        let ply = "ply\nformat binary_little_endian 1.0\n\
+element vertex 4000000000000\nproperty float x\nproperty float y\nproperty float z\n\
+end_header\n";
+        assert!(load_bytes("huge-count", ply.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+use super::invalid_data;
+use crate::materials::Material;
+use crate::objects::mesh::position_key;
+use crate::objects::mesh::TriangleMesh;
+
+use cgmath::Point3;
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+// Loads an ASCII or binary STL file. STL only stores faceted normals, so when a
+// `crease_angle` (in degrees) is given smooth vertex normals are computed instead.
+// Refer: http://www.fabbers.com/tech/STL_Format
+pub fn load<P: AsRef<Path>>(
+    path: P,
+    material: Arc<Material>,
+    crease_angle: Option<f64>,
+) -> io::Result<TriangleMesh> {
+    let path = path.as_ref();
+    let file_size = path.metadata()?.len();
+    let reader = BufReader::new(File::open(path)?);
+    read(reader, file_size, path, material, crease_angle)
+}
+
+// Reads an STL file of `file_size` bytes from `reader`. `path` is only used in error messages.
+fn read<R: BufRead>(
+    mut reader: R,
+    file_size: u64,
+    path: &Path,
+    material: Arc<Material>,
+    crease_angle: Option<f64>,
+) -> io::Result<TriangleMesh> {
+    let mut mesh = TriangleMesh::new(material);
+    let mut vertices: HashMap<[u64; 3], u32> = HashMap::new();
+    let mut add_triangle = |mesh: &mut TriangleMesh, triangle: [Point3<f64>; 3]| {
+        let mut indices = [0; 3];
+        for (index, p) in indices.iter_mut().zip(triangle.iter()) {
+            let next = mesh.positions.len() as u32;
+            *index = *vertices.entry(position_key(p)).or_insert(next);
+            if *index == next {
+                mesh.positions.push(*p);
+            }
+        }
+        mesh.add_triangle(indices, 0);
+    };
+
+    // Binary files may also start with "solid", so the size is checked first
+    let mut header = [0u8; 84];
+    let header_len = read_up_to(&mut reader, &mut header)?;
+    let count = u32::from_le_bytes([header[80], header[81], header[82], header[83]]);
+    let binary = header_len == 84 && file_size == 84 + 50 * u64::from(count);
+
+    if binary {
+        mesh.indices.reserve_exact(count as usize);
+        mesh.face_materials.reserve_exact(count as usize);
+        let mut record = [0u8; 50];
+        for _ in 0..count {
+            reader.read_exact(&mut record)?;
+            let float = |i: usize| {
+                let offset = 12 + 4 * i;
+                f64::from(f32::from_le_bytes([
+                    record[offset],
+                    record[offset + 1],
+                    record[offset + 2],
+                    record[offset + 3],
+                ]))
+            };
+            let triangle = [
+                Point3::new(float(0), float(1), float(2)),
+                Point3::new(float(3), float(4), float(5)),
+                Point3::new(float(6), float(7), float(8)),
+            ];
+            add_triangle(&mut mesh, triangle);
+        }
+    } else {
+        if !header[..header_len].starts_with(b"solid") {
+            return Err(invalid_data(format!("{}: not an STL file", path.display())));
+        }
+
+        let reader = io::Cursor::new(header[..header_len].to_vec()).chain(reader);
+        let mut triangle: Vec<Point3<f64>> = Vec::with_capacity(3);
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let error =
+                || invalid_data(format!("{}:{}: invalid vertex", path.display(), number + 1));
+            let mut tokens = line.split_whitespace();
+
+            match tokens.next() {
+                Some("vertex") => {
+                    let mut coordinate = || -> io::Result<f64> {
+                        tokens.next().and_then(|t| t.parse().ok()).ok_or_else(error)
+                    };
+                    let p = Point3::new(coordinate()?, coordinate()?, coordinate()?);
+                    triangle.push(p);
+                }
+                Some("endloop") => {
+                    if triangle.len() != 3 {
+                        return Err(invalid_data(format!(
+                            "{}:{}: facet does not have three vertices",
+                            path.display(),
+                            number + 1
+                        )));
+                    }
+                    add_triangle(&mut mesh, [triangle[0], triangle[1], triangle[2]]);
+                    triangle.clear();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(crease_angle) = crease_angle {
+        mesh.compute_normals(crease_angle);
+    }
+
+    Ok(mesh)
+}
+
+fn read_up_to<R: Read>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut len = 0;
+    while len < buffer.len() {
+        match reader.read(&mut buffer[len..])? {
+            0 => break,
+            n => len += n,
+        }
+    }
+
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    use cgmath::InnerSpace;
+
+    use std::io::Cursor;
+
+    const TETRAHEDRON: [[[f32; 3]; 3]; 4] = [
+        [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+        [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]],
+        [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+        [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+    ];
+
+    fn ascii(triangles: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut stl = String::from("solid tetrahedron\n");
+        for triangle in triangles {
+            stl += "  facet normal 0 0 0\n    outer loop\n";
+            for p in triangle {
+                stl += &format!("      vertex {} {} {}\n", p[0], p[1], p[2]);
+            }
+            stl += "    endloop\n  endfacet\n";
+        }
+        stl += "endsolid tetrahedron\n";
+        stl.into_bytes()
+    }
+
+    fn binary(header: &[u8], triangles: &[[[f32; 3]; 3]]) -> Vec<u8> {
+        let mut stl = header.to_vec();
+        stl.resize(80, 0);
+        stl.extend_from_slice(&(triangles.len() as u32).to_le_bytes());
+        for triangle in triangles {
+            stl.extend_from_slice(&[0; 12]);
+            for value in triangle.iter().flatten() {
+                stl.extend_from_slice(&value.to_le_bytes());
+            }
+            stl.extend_from_slice(&[0; 2]);
+        }
+        stl
+    }
+
+    fn load_bytes(name: &str, bytes: &[u8], crease_angle: Option<f64>) -> io::Result<TriangleMesh> {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        let path = Path::new(name);
+        read(
+            Cursor::new(bytes),
+            bytes.len() as u64,
+            path,
+            material,
+            crease_angle,
+        )
+    }
+
+    // Every triangle comes back in order, with shared corners welded into one vertex
+    fn assert_tetrahedron(mesh: &TriangleMesh) {
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.len(), 4);
+        for (face, triangle) in mesh.indices.iter().zip(TETRAHEDRON.iter()) {
+            for (&index, p) in face.iter().zip(triangle) {
+                let expected = Point3::new(f64::from(p[0]), f64::from(p[1]), f64::from(p[2]));
+                assert_eq!(mesh.positions[index as usize], expected);
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_ascii() {
+        let mesh = load_bytes("ascii", &ascii(&TETRAHEDRON), None).unwrap();
+        assert_tetrahedron(&mesh);
+        assert!(mesh.normals.is_none());
+    }
+
+    #[test]
+    fn round_trips_binary() {
+        let mesh = load_bytes("binary", &binary(b"binary", &TETRAHEDRON), None).unwrap();
+        assert_tetrahedron(&mesh);
+    }
+
+    #[test]
+    fn reads_binary_starting_with_solid() {
+        let stl = binary(b"solid but binary", &TETRAHEDRON);
+        let mesh = load_bytes("binary-solid", &stl, None).unwrap();
+        assert_tetrahedron(&mesh);
+    }
+
+    #[test]
+    fn computes_normals_with_a_crease_angle() {
+        let mesh = load_bytes("normals", &ascii(&TETRAHEDRON), Some(30.0)).unwrap();
+        let normals = mesh.normals.unwrap();
+        assert_eq!(normals.len(), mesh.positions.len());
+        assert!(normals.iter().all(|n| (n.magnitude() - 1.0).abs() < 1e-9));
+    }
+
+    #[test]
+    fn rejects_broken_files() {
+        let short = b"solid t\nfacet normal 0 0 0\nouter loop\nvertex 0 0 0\nvertex 1 0 0\n\
+                      endloop\nendfacet\nendsolid t\n";
+        assert!(load_bytes("short-facet", short, None).is_err());
+        let bad = b"solid t\nfacet normal 0 0 0\nouter loop\nvertex 0 0 x\n";
+        assert!(load_bytes("bad-vertex", bad, None).is_err());
+        assert!(load_bytes("not-stl", b"hello", None).is_err());
+    }
+}
@@ -23,6 +23,6 @@ pub fn write_img(img: &[Vec<Vector3<f64>>], filename: &str) {
         }
     }
 
-    let _ = image::ImageRgb8(imgbuf).save(&path);
+    let _ = image::ImageRgb8(imgbuf).save(path);
     println!("Successfully wrote to {}", display);
 }
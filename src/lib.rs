@@ -3,6 +3,7 @@ pub mod bvh;
 pub mod io;
 pub mod materials;
 pub mod objects;
+pub mod polynomial;
 pub mod ray;
 pub mod scene;
 pub mod textures;
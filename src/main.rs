@@ -1,3 +1,4 @@
+use raytrac::io::gltf;
 use raytrac::materials::dielectric::Dielectric;
 use raytrac::materials::lambertian::Lambertian;
 use raytrac::materials::light::DiffuseLight;
@@ -13,7 +14,9 @@ use cgmath::Point3;
 use cgmath::Vector3;
 use rand::prelude::*;
 
+use std::env;
 use std::f64;
+use std::process;
 use std::sync::Arc;
 
 fn main() {
@@ -36,9 +39,24 @@ fn main() {
         dist_to_focus,
     );
 
-    let mut world = random_scene();
+    // A glTF file given on the command line replaces the random scene
+    let (camera, world) = match env::args().nth(1) {
+        Some(path) => {
+            let aspect = f64::from(WIDTH) / f64::from(HEIGHT);
+            let gltf = gltf::load(&path, aspect).unwrap_or_else(|e| {
+                eprintln!("Failed to load {}: {}", path, e);
+                process::exit(1)
+            });
+            for warning in &gltf.warnings {
+                eprintln!("Warning: {}: {}", path, warning);
+            }
+            let camera = gltf.cameras.first().cloned().unwrap_or(camera);
+            (camera, gltf.objects)
+        }
+        None => (camera, random_scene()),
+    };
 
-    let scene = Scene::new(camera, WIDTH, HEIGHT, SAMPLES, &mut world, 0.0, 1.0);
+    let scene = Scene::new(camera, WIDTH, HEIGHT, SAMPLES, world, 0.0, 1.0);
     scene.render("output/sample.png")
 }
 
@@ -0,0 +1,279 @@
+use crate::objects::HitRecord;
+use crate::ray::Ray;
+use crate::textures::Texture;
+use crate::textures::Textured;
+
+use super::Scatterable;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+use rand::prelude::*;
+
+use std::f64::consts::PI;
+
+// Smallest GGX alpha, since a perfect mirror has no spread of facet normals to sample
+const MIN_ALPHA: f64 = 1e-4;
+// Reflectances are kept this far inside (0, 1), where a complex index still fits them
+const REFLECTANCE_MARGIN: f64 = 1e-4;
+
+// A rough metal. Its surface is made of tiny mirror facets whose normals follow the GGX
+// distribution, and facets hide each other as in Smith's model. Each channel reflects by the
+// Fresnel equations for the complex refractive index eta + ik of the metal, for red, green and
+// blue light.
+// Refer: http://www.pbr-book.org/3ed-2018/Reflection_Models/Microfacet_Models.html
+pub struct Conductor {
+    index: ComplexIndex,
+    // From 0 for a mirror to 1 for a very rough surface, the average of the texture channels
+    roughness: Texture,
+}
+
+enum ComplexIndex {
+    // Measured eta and k
+    Measured(Vector3<f64>, Vector3<f64>),
+    // Fit to the reflectance at normal incidence, which may vary over the surface
+    Reflectance(Texture),
+}
+
+impl Conductor {
+    pub fn new(eta: Vector3<f64>, k: Vector3<f64>, roughness: Texture) -> Self {
+        Conductor {
+            index: ComplexIndex::Measured(eta, k),
+            roughness,
+        }
+    }
+
+    // A metal given by its color, the reflectance at normal incidence, as in glTF. Its index is
+    // fit so that it turns white towards grazing angles.
+    // Refer: Gulbrandsen, "Artist Friendly Metallic Fresnel" (JCGT 2014)
+    pub fn reflectance(color: Texture, roughness: Texture) -> Self {
+        Conductor {
+            index: ComplexIndex::Reflectance(color),
+            roughness,
+        }
+    }
+
+    pub fn gold(roughness: Texture) -> Self {
+        Conductor::new(
+            vec3(0.143, 0.374, 1.442),
+            vec3(3.983, 2.385, 1.603),
+            roughness,
+        )
+    }
+
+    pub fn copper(roughness: Texture) -> Self {
+        Conductor::new(
+            vec3(0.200, 0.924, 1.102),
+            vec3(3.912, 2.452, 2.142),
+            roughness,
+        )
+    }
+
+    pub fn aluminium(roughness: Texture) -> Self {
+        Conductor::new(
+            vec3(1.657, 0.880, 0.521),
+            vec3(9.224, 6.270, 4.837),
+            roughness,
+        )
+    }
+
+    pub fn silver(roughness: Texture) -> Self {
+        Conductor::new(
+            vec3(0.155, 0.117, 0.138),
+            vec3(4.828, 3.122, 2.147),
+            roughness,
+        )
+    }
+
+    fn fresnel(&self, cos_theta: f64, rec: &HitRecord) -> Vector3<f64> {
+        let (eta, k) = match self.index {
+            ComplexIndex::Measured(eta, k) => (eta, k),
+            ComplexIndex::Reflectance(ref color) => {
+                let color = color.value(rec.u, rec.v, rec.p);
+                let fit = index_from_reflectance;
+                let (x, y, z) = (fit(color.x), fit(color.y), fit(color.z));
+                (vec3(x.0, y.0, z.0), vec3(x.1, y.1, z.1))
+            }
+        };
+
+        vec3(
+            fresnel_conductor(cos_theta, eta.x, k.x),
+            fresnel_conductor(cos_theta, eta.y, k.y),
+            fresnel_conductor(cos_theta, eta.z, k.z),
+        )
+    }
+}
+
+impl Scatterable for Conductor {
+    // Picks a facet among those facing the ray and mirrors the ray off it. The facet choice
+    // cancels the distribution out of the weight, which leaves the Fresnel reflectance and the
+    // chance that the mirrored ray is not shadowed.
+    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
+        let roughness = self.roughness.value(rec.u, rec.v, rec.p);
+        let roughness = ((roughness.x + roughness.y + roughness.z) / 3.0).clamp(0.0, 1.0);
+        let alpha = (roughness * roughness).max(MIN_ALPHA);
+
+        let normal = rec.normal;
+        let helper = if normal.x.abs() > 0.9 {
+            vec3(0.0, 1.0, 0.0)
+        } else {
+            vec3(1.0, 0.0, 0.0)
+        };
+        let tangent = normal.cross(helper).normalize();
+        let bitangent = normal.cross(tangent);
+
+        let to_local = |w: Vector3<f64>| vec3(w.dot(tangent), w.dot(bitangent), w.dot(normal));
+        let wo = to_local(-ray.direction.normalize());
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        let mut rng = thread_rng();
+        let m = sample_visible_normal(wo, alpha, rng.gen::<f64>(), rng.gen::<f64>());
+        let cos_theta = wo.dot(m);
+        let wi = 2.0 * cos_theta * m - wo;
+        if wi.z <= 0.0 {
+            return None;
+        }
+
+        let direction = tangent * wi.x + bitangent * wi.y + normal * wi.z;
+        // Shading normals can still send the ray into the surface
+        if direction.dot(rec.geometric_normal) <= 0.0 {
+            return None;
+        }
+
+        // Height-correlated masking and shadowing, over the masking already in the facet choice
+        let (lambda_o, lambda_i) = (smith_lambda(wo, alpha), smith_lambda(wi, alpha));
+        let shadowing = (1.0 + lambda_o) / (1.0 + lambda_o + lambda_i);
+
+        let scattered = Ray::from(Point3::from_vec(rec.p), direction, ray.time);
+        Some((scattered, self.fresnel(cos_theta, rec) * shadowing))
+    }
+}
+
+// Samples a facet normal in proportion to how much of it `wo` sees, in the frame where the
+// surface normal is z
+// Refer: http://jcgt.org/published/0007/04/01/
+fn sample_visible_normal(wo: Vector3<f64>, alpha: f64, u1: f64, u2: f64) -> Vector3<f64> {
+    // Stretched to the view of a hemisphere
+    let vh = vec3(alpha * wo.x, alpha * wo.y, wo.z).normalize();
+    let length2 = vh.x * vh.x + vh.y * vh.y;
+    let t1 = if length2 > 0.0 {
+        vec3(-vh.y, vh.x, 0.0) / length2.sqrt()
+    } else {
+        vec3(1.0, 0.0, 0.0)
+    };
+    let t2 = vh.cross(t1);
+
+    // A point on the disk, squeezed onto the part of the hemisphere seen from `vh`
+    let r = u1.sqrt();
+    let phi = 2.0 * PI * u2;
+    let p1 = r * phi.cos();
+    let s = 0.5 * (1.0 + vh.z);
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+    let nh = t1 * p1 + t2 * p2 + vh * (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+    vec3(alpha * nh.x, alpha * nh.y, nh.z.max(1e-6)).normalize()
+}
+
+// The area of facets hidden from `w` over the area facing it
+fn smith_lambda(w: Vector3<f64>, alpha: f64) -> f64 {
+    let cos2 = w.z * w.z;
+    let tan2 = (1.0 - cos2).max(0.0) / cos2;
+    0.5 * ((1.0 + alpha * alpha * tan2).sqrt() - 1.0)
+}
+
+// The eta and k of a metal reflecting `r` at normal incidence and everything at grazing angles
+fn index_from_reflectance(r: f64) -> (f64, f64) {
+    let r = r.clamp(REFLECTANCE_MARGIN, 1.0 - REFLECTANCE_MARGIN);
+    ((1.0 - r) / (1.0 + r), 2.0 * r.sqrt() / (1.0 + r))
+}
+
+// Unpolarized reflectance of a metal with refractive index eta + ik, from air
+fn fresnel_conductor(cos_theta: f64, eta: f64, k: f64) -> f64 {
+    let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+    let sin2 = 1.0 - cos2;
+    let (eta2, k2) = (eta * eta, k * k);
+
+    let t0 = eta2 - k2 - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2.0 * cos_theta * a;
+    let rs = (t1 - t2) / (t1 + t2);
+
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let rp = rs * (t3 - t4) / (t3 + t4);
+
+    0.5 * (rs + rp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METALS: [(f64, f64); 5] = [
+        (0.143, 3.983),
+        (0.2, 3.9),
+        (0.5, 0.0),
+        (1.5, 0.0),
+        (2.5, 1.2),
+    ];
+
+    #[test]
+    fn fresnel_matches_the_closed_forms() {
+        for &(eta, k) in &METALS {
+            let normal = ((eta - 1.0).powi(2) + k * k) / ((eta + 1.0).powi(2) + k * k);
+            assert!((fresnel_conductor(1.0, eta, k) - normal).abs() < 1e-12);
+            assert!((fresnel_conductor(0.0, eta, k) - 1.0).abs() < 1e-12);
+
+            for i in 0..=100 {
+                let f = fresnel_conductor(f64::from(i) / 100.0, eta, k);
+                assert!(
+                    (0.0..=1.0).contains(&f),
+                    "F = {} for eta {} and k {}",
+                    f,
+                    eta,
+                    k
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn fits_the_reflectance_at_normal_incidence() {
+        for i in 0..=20 {
+            let r = f64::from(i) / 20.0;
+            let (eta, k) = index_from_reflectance(r);
+            let expected = r.clamp(REFLECTANCE_MARGIN, 1.0 - REFLECTANCE_MARGIN);
+            assert!((fresnel_conductor(1.0, eta, k) - expected).abs() < 1e-12);
+            assert!((fresnel_conductor(0.0, eta, k) - 1.0).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn samples_visible_normals_in_the_upper_hemisphere() {
+        for &alpha in &[MIN_ALPHA, 0.05, 0.3, 1.0] {
+            for &theta in &[0.0, 0.3, 1.0, 1.5, PI / 2.0 - 1e-6] {
+                for &phi in &[0.0, 1.0, 2.5, 4.0] {
+                    let wo = vec3(
+                        theta.sin() * f64::cos(phi),
+                        theta.sin() * f64::sin(phi),
+                        theta.cos(),
+                    );
+                    for i in 0..=16 {
+                        for j in 0..=16 {
+                            let (u1, u2) = (f64::from(i) / 16.0, f64::from(j) / 16.0);
+                            let m = sample_visible_normal(wo, alpha, u1, u2);
+                            assert!((m.magnitude() - 1.0).abs() < 1e-9);
+                            assert!(m.z > 0.0, "{:?} from {:?} with alpha {}", m, wo, alpha);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -28,34 +28,26 @@ impl Scatterable for Dielectric {
         let mut refracted = Vector3::new(0.0, 0.0, 0.0);
         let reflect_prob: f64;
 
-        let (outward_normal, ni_over_nt, cosine) = if dot(ray.direction, rec.normal) > 0.0 {
-            (
-                -rec.normal,
-                self.refractive_index,
-                self.refractive_index * dot(ray.direction, rec.normal) / ray.direction.magnitude(),
-            )
+        let cosine = -dot(ray.direction, rec.normal) / ray.direction.magnitude();
+        let (ni_over_nt, cosine) = if rec.front_face {
+            (1.0 / self.refractive_index, cosine)
         } else {
-            (
-                rec.normal,
-                1.0 / self.refractive_index,
-                -1.0 * ray.direction.dot(rec.normal) / ray.direction.magnitude(),
-            )
+            (self.refractive_index, self.refractive_index * cosine)
         };
 
-        if let Some(r) = refract(ray.direction, outward_normal, ni_over_nt) {
+        if let Some(r) = refract(ray.direction, rec.normal, ni_over_nt) {
             refracted = r;
             reflect_prob = schlick(cosine, self.refractive_index);
         } else {
             reflect_prob = 1.0;
         }
 
-        let scattered;
         let mut rng = thread_rng();
-        if rng.gen::<f64>() < reflect_prob {
-            scattered = Ray::from(Point3::from_vec(rec.p), reflected, ray.time);
+        let scattered = if rng.gen::<f64>() < reflect_prob {
+            Ray::from(Point3::from_vec(rec.p), reflected, ray.time)
         } else {
-            scattered = Ray::from(Point3::from_vec(rec.p), refracted, ray.time);
-        }
+            Ray::from(Point3::from_vec(rec.p), refracted, ray.time)
+        };
 
         Some((scattered, attenuation))
     }
@@ -0,0 +1,411 @@
+use crate::objects::HitRecord;
+use crate::ray::Ray;
+
+use super::Scatterable;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+use rand::prelude::*;
+
+use std::f64::consts::LN_2;
+use std::f64::consts::PI;
+
+// Number of scattering events modelled as separate lobes: R, TT and TRT. Longer paths are
+// lumped into a final isotropic lobe.
+const P_MAX: usize = 3;
+
+// Absorption coefficients of the two pigments that give hair its color
+const EUMELANIN_SIGMA_A: [f64; 3] = [0.419, 0.697, 1.37];
+const PHEOMELANIN_SIGMA_A: [f64; 3] = [0.187, 0.4, 1.05];
+
+// The hair scattering model of Chiang et al., treating a fiber as a rough dielectric cylinder
+// with tilted cuticle scales. It expects the hit tangent to run along the fiber and v to
+// measure the offset across it, as reported by `Curve`.
+// Refer: http://www.pbr-book.org/3ed-2018/Materials/Hair.html
+pub struct Hair {
+    sigma_a: Vector3<f64>,
+    eta: f64,
+    // Longitudinal variance of each lobe
+    v: [f64; P_MAX + 1],
+    // Azimuthal logistic scale
+    s: f64,
+    // sin and cos of 2^k times the scale tilt, for the R, TT and TRT lobes
+    sin_2k_alpha: [f64; 3],
+    cos_2k_alpha: [f64; 3],
+}
+
+impl Hair {
+    // `sigma_a` is the absorption inside the fiber, `beta_m` and `beta_n` the longitudinal and
+    // azimuthal roughness in [0, 1] and `alpha` the tilt of the cuticle scales in degrees
+    pub fn new(sigma_a: Vector3<f64>, beta_m: f64, beta_n: f64, alpha: f64) -> Self {
+        let beta_m = beta_m.clamp(0.01, 1.0);
+        let beta_n = beta_n.clamp(0.01, 1.0);
+
+        let v0 = (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20)).powi(2);
+        let v = [v0, 0.25 * v0, 4.0 * v0, 4.0 * v0];
+        let s =
+            (PI / 8.0).sqrt() * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22));
+
+        let mut sin_2k_alpha = [alpha.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [safe_sqrt(1.0 - sin_2k_alpha[0].powi(2)), 0.0, 0.0];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1].powi(2) - sin_2k_alpha[i - 1].powi(2);
+        }
+
+        Hair {
+            sigma_a,
+            eta: 1.55,
+            v,
+            s,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        }
+    }
+
+    // Colors the fiber by its pigment concentrations. Black hair has an eumelanin
+    // concentration around 8, brown around 1.3 and blonde around 0.3. Pheomelanin makes
+    // hair red.
+    pub fn melanin(eumelanin: f64, pheomelanin: f64, beta_m: f64, beta_n: f64, alpha: f64) -> Self {
+        let sigma_a = vec3(
+            eumelanin * EUMELANIN_SIGMA_A[0] + pheomelanin * PHEOMELANIN_SIGMA_A[0],
+            eumelanin * EUMELANIN_SIGMA_A[1] + pheomelanin * PHEOMELANIN_SIGMA_A[1],
+            eumelanin * EUMELANIN_SIGMA_A[2] + pheomelanin * PHEOMELANIN_SIGMA_A[2],
+        );
+        Hair::new(sigma_a, beta_m, beta_n, alpha)
+    }
+
+    // Picks the absorption that makes many fibers together come out in roughly `color`
+    pub fn color(color: Vector3<f64>, beta_m: f64, beta_n: f64, alpha: f64) -> Self {
+        let beta_n_clamped = beta_n.clamp(0.01, 1.0);
+        let denominator = 5.969 - 0.215 * beta_n_clamped + 2.532 * beta_n_clamped.powi(2)
+            - 10.73 * beta_n_clamped.powi(3)
+            + 5.574 * beta_n_clamped.powi(4)
+            + 0.245 * beta_n_clamped.powi(5);
+        let sigma_a = color.map(|c| (c.clamp(1e-4, 1.0).ln() / denominator).powi(2));
+        Hair::new(sigma_a, beta_m, beta_n, alpha)
+    }
+
+    // The direction of the outgoing ray after tilting it by the cuticle scales for lobe p
+    fn tilt(&self, p: usize, sin_theta_o: f64, cos_theta_o: f64) -> (f64, f64) {
+        let (sin_theta, cos_theta) = match p {
+            0 => (
+                sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+
+        (sin_theta, cos_theta.abs())
+    }
+
+    // Attenuation of each lobe for a ray leaving at `cos_theta_o` with offset h
+    fn attenuations(&self, cos_theta_o: f64, h: f64) -> [Vector3<f64>; P_MAX + 1] {
+        let sin_theta_o = safe_sqrt(1.0 - cos_theta_o * cos_theta_o);
+        let sin_theta_t = sin_theta_o / self.eta;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        let sin_gamma_t = h / etap;
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+
+        // Transmittance of a single path across the fiber
+        let transmittance = (-self.sigma_a * (2.0 * cos_gamma_t / cos_theta_t)).map(f64::exp);
+        let cos_gamma_o = safe_sqrt(1.0 - h * h);
+        let f = fresnel(cos_theta_o * cos_gamma_o, self.eta);
+
+        let r = vec3(f, f, f);
+        let tt = transmittance * (1.0 - f).powi(2);
+        let trt = tt.mul_element_wise(transmittance) * f;
+        let rest = (trt.mul_element_wise(transmittance) * f)
+            .div_element_wise(vec3(1.0, 1.0, 1.0) - transmittance * f);
+
+        [r, tt, trt, rest]
+    }
+
+    // Evaluates the BSDF times the cosine of the incoming direction, and its pdf when sampled
+    // by `sample`, for directions in the fiber's frame where x runs along the fiber
+    fn evaluate(&self, wo: Vector3<f64>, wi: Vector3<f64>, h: f64) -> (Vector3<f64>, f64) {
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z.atan2(wo.y);
+        let sin_theta_i = wi.x;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi_i = wi.z.atan2(wi.y);
+
+        let gamma_o = safe_asin(h);
+        let gamma_t = self.gamma_t(sin_theta_o, cos_theta_o, h);
+        let attenuations = self.attenuations(cos_theta_o, h);
+        let weights = lobe_weights(&attenuations);
+        let phi = phi_i - phi_o;
+
+        let mut f = Vector3::zero();
+        let mut pdf = 0.0;
+        for p in 0..=P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+            let mp = longitudinal(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.v[p],
+            );
+            let np = if p < P_MAX {
+                azimuthal(phi, p, self.s, gamma_o, gamma_t)
+            } else {
+                1.0 / (2.0 * PI)
+            };
+
+            f += attenuations[p] * (mp * np);
+            pdf += weights[p] * mp * np;
+        }
+
+        (f, pdf)
+    }
+
+    fn gamma_t(&self, sin_theta_o: f64, cos_theta_o: f64, h: f64) -> f64 {
+        let etap = (self.eta * self.eta - sin_theta_o * sin_theta_o).sqrt() / cos_theta_o;
+        safe_asin(h / etap)
+    }
+
+    // Picks a lobe by its share of the reflected light, then samples its longitudinal and
+    // azimuthal distributions
+    fn sample(&self, wo: Vector3<f64>, h: f64) -> Vector3<f64> {
+        let mut rng = thread_rng();
+        let sin_theta_o = wo.x;
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z.atan2(wo.y);
+
+        let weights = lobe_weights(&self.attenuations(cos_theta_o, h));
+        let mut choice = rng.gen::<f64>();
+        let mut p = 0;
+        while p < P_MAX && choice >= weights[p] {
+            choice -= weights[p];
+            p += 1;
+        }
+
+        let (sin_theta_op, cos_theta_op) = self.tilt(p, sin_theta_o, cos_theta_o);
+        let u = rng.gen::<f64>().max(1e-5);
+        let v = self.v[p];
+        let cos_theta = 1.0 + v * (u + (1.0 - u) * (-2.0 / v).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * PI * rng.gen::<f64>()).cos();
+        let sin_theta_i = -cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op;
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        let dphi = if p < P_MAX {
+            let gamma_o = safe_asin(h);
+            let gamma_t = self.gamma_t(sin_theta_o, cos_theta_o, h);
+            shift(p, gamma_o, gamma_t) + sample_trimmed_logistic(rng.gen::<f64>(), self.s)
+        } else {
+            2.0 * PI * rng.gen::<f64>()
+        };
+        let phi_i = phi_o + dphi;
+
+        vec3(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+impl Scatterable for Hair {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
+        // Shapes without a tangent get an arbitrary one around the normal
+        let normal = rec.normal;
+        let tangent = rec.tangent.unwrap_or_else(|| {
+            let helper = if normal.x.abs() > 0.9 {
+                vec3(0.0, 1.0, 0.0)
+            } else {
+                vec3(1.0, 0.0, 0.0)
+            };
+            normal.cross(helper).normalize()
+        });
+        let bitangent = normal.cross(tangent).normalize();
+        let normal = tangent.cross(bitangent);
+
+        let to_local = |w: Vector3<f64>| vec3(w.dot(tangent), w.dot(bitangent), w.dot(normal));
+        let wo = to_local(-ray.direction.normalize());
+        let h = (2.0 * rec.v - 1.0).clamp(-1.0, 1.0);
+
+        let wi = self.sample(wo, h);
+        let (f, pdf) = self.evaluate(wo, wi, h);
+        if pdf.is_nan() || pdf <= 0.0 {
+            return None;
+        }
+
+        let direction = tangent * wi.x + bitangent * wi.y + normal * wi.z;
+        let scattered = Ray::from(Point3::from_vec(rec.p), direction, ray.time);
+        Some((scattered, f / pdf))
+    }
+}
+
+// Share of each lobe in the light leaving the fiber, by luminance
+fn lobe_weights(attenuations: &[Vector3<f64>; P_MAX + 1]) -> [f64; P_MAX + 1] {
+    let luminance = |c: &Vector3<f64>| 0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z;
+    let total: f64 = attenuations.iter().map(luminance).sum();
+    let mut weights = [0.0; P_MAX + 1];
+    if total > 0.0 {
+        for (weight, attenuation) in weights.iter_mut().zip(attenuations.iter()) {
+            *weight = luminance(attenuation) / total;
+        }
+    }
+
+    weights
+}
+
+fn longitudinal(
+    cos_theta_i: f64,
+    cos_theta_o: f64,
+    sin_theta_i: f64,
+    sin_theta_o: f64,
+    v: f64,
+) -> f64 {
+    let a = cos_theta_i * cos_theta_o / v;
+    let b = sin_theta_i * sin_theta_o / v;
+    // The direct form overflows for low roughness
+    if v <= 0.1 {
+        (log_bessel_i0(a) - b - 1.0 / v + LN_2 + (1.0 / (2.0 * v)).ln()).exp()
+    } else {
+        (-b).exp() * bessel_i0(a) / ((1.0 / v).sinh() * 2.0 * v)
+    }
+}
+
+fn azimuthal(phi: f64, p: usize, s: f64, gamma_o: f64, gamma_t: f64) -> f64 {
+    let mut dphi = phi - shift(p, gamma_o, gamma_t);
+    while dphi > PI {
+        dphi -= 2.0 * PI;
+    }
+    while dphi < -PI {
+        dphi += 2.0 * PI;
+    }
+
+    trimmed_logistic(dphi, s)
+}
+
+// Change in azimuth of a ray that leaves after p internal paths
+fn shift(p: usize, gamma_o: f64, gamma_t: f64) -> f64 {
+    let p = p as f64;
+    2.0 * p * gamma_t - 2.0 * gamma_o + p * PI
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 {
+    1.0 / (1.0 + (-x / s).exp())
+}
+
+// The logistic distribution limited to [-π, π]
+fn trimmed_logistic(x: f64, s: f64) -> f64 {
+    logistic(x, s) / (logistic_cdf(PI, s) - logistic_cdf(-PI, s))
+}
+
+fn sample_trimmed_logistic(u: f64, s: f64) -> f64 {
+    let k = logistic_cdf(PI, s) - logistic_cdf(-PI, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(-PI, s)) - 1.0).ln();
+    x.clamp(-PI, PI)
+}
+
+// Modified Bessel function of the first kind of order zero
+fn bessel_i0(x: f64) -> f64 {
+    let mut value = 0.0;
+    let mut x2i = 1.0;
+    let mut factorial: f64 = 1.0;
+    let mut i4 = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= f64::from(i);
+        }
+        value += x2i / (i4 * factorial * factorial);
+        x2i *= x * x;
+        i4 *= 4.0;
+    }
+
+    value
+}
+
+fn log_bessel_i0(x: f64) -> f64 {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        bessel_i0(x).ln()
+    }
+}
+
+// Reflectance of an unpolarized ray entering a dielectric of index `eta` from air
+fn fresnel(cos_theta_i: f64, eta: f64) -> f64 {
+    let cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let (eta_i, eta_t, cos_theta_i) = if cos_theta_i > 0.0 {
+        (1.0, eta, cos_theta_i)
+    } else {
+        (eta, 1.0, -cos_theta_i)
+    };
+
+    let sin_theta_t = eta_i / eta_t * safe_sqrt(1.0 - cos_theta_i * cos_theta_i);
+    if sin_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+
+    let parallel =
+        (eta_t * cos_theta_i - eta_i * cos_theta_t) / (eta_t * cos_theta_i + eta_i * cos_theta_t);
+    let perpendicular =
+        (eta_i * cos_theta_i - eta_t * cos_theta_t) / (eta_i * cos_theta_i + eta_t * cos_theta_t);
+    (parallel * parallel + perpendicular * perpendicular) / 2.0
+}
+
+fn safe_sqrt(x: f64) -> f64 {
+    x.max(0.0).sqrt()
+}
+
+fn safe_asin(x: f64) -> f64 {
+    x.clamp(-1.0, 1.0).asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evaluates_sampled_directions_with_a_positive_pdf() {
+        let hairs = [
+            Hair::melanin(1.3, 0.0, 0.3, 0.3, 2.0),
+            Hair::melanin(8.0, 0.0, 0.05, 0.05, 2.0),
+            Hair::color(vec3(0.9, 0.6, 0.3), 0.8, 0.8, 0.0),
+        ];
+        let outgoing = [
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.5, 0.5, 0.5).normalize(),
+            vec3(-0.9, 0.1, 0.3).normalize(),
+        ];
+
+        for hair in &hairs {
+            for &wo in &outgoing {
+                for &h in &[-0.9, 0.0, 0.5] {
+                    for _ in 0..200 {
+                        let wi = hair.sample(wo, h);
+                        assert!((wi.magnitude() - 1.0).abs() < 1e-9);
+                        let (f, pdf) = hair.evaluate(wo, wi, h);
+                        assert!(pdf.is_finite() && pdf > 0.0, "pdf {} for {:?}", pdf, wi);
+                        for a in 0..3 {
+                            assert!(f[a].is_finite() && f[a] >= 0.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
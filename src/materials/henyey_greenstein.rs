@@ -0,0 +1,66 @@
+use crate::objects::HitRecord;
+use crate::ray::Ray;
+use crate::textures::Texture;
+use crate::textures::Textured;
+
+use super::Scatterable;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+use rand::prelude::*;
+
+use std::f64::consts::PI;
+
+// Anisotropic phase function for participating media. Positive `g` scatters light forward,
+// negative `g` scatters it back and zero is isotropic.
+// Refer: http://www.pbr-book.org/3ed-2018/Volume_Scattering/Phase_Functions.html
+pub struct HenyeyGreenstein {
+    albedo: Texture,
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(albedo: Texture, g: f64) -> Self {
+        HenyeyGreenstein {
+            albedo,
+            g: g.clamp(-0.999, 0.999),
+        }
+    }
+
+    fn sample_cos_theta(&self) -> f64 {
+        let xi = random::<f64>();
+        if self.g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let g = self.g;
+            let sqr_term = (1.0 - g * g) / (1.0 - g + 2.0 * g * xi);
+            (1.0 + g * g - sqr_term * sqr_term) / (2.0 * g)
+        }
+    }
+}
+
+impl Scatterable for HenyeyGreenstein {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
+        let forward = ray.direction.normalize();
+        let cos_theta = self.sample_cos_theta();
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let phi = 2.0 * PI * random::<f64>();
+
+        let helper = if forward.x.abs() > 0.9 {
+            vec3(0.0, 1.0, 0.0)
+        } else {
+            vec3(1.0, 0.0, 0.0)
+        };
+        let u = forward.cross(helper).normalize();
+        let v = forward.cross(u);
+        let direction =
+            u * (sin_theta * phi.cos()) + v * (sin_theta * phi.sin()) + forward * cos_theta;
+
+        let scattered = Ray::from(Point3::from_vec(rec.p), direction, ray.time);
+        let attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+
+        Some((scattered, attenuation))
+    }
+}
@@ -0,0 +1,37 @@
+use crate::objects::HitRecord;
+use crate::ray::Ray;
+use crate::textures::constant_texture::ConstantTexture;
+use crate::textures::Texture;
+use crate::textures::Textured;
+
+use super::{random_unit_vector, Scatterable};
+
+use cgmath::prelude::*;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+// Phase function of a participating medium that scatters uniformly in all directions
+pub struct Isotropic {
+    albedo: Texture,
+}
+
+impl Isotropic {
+    pub fn new(albedo: Texture) -> Self {
+        Isotropic { albedo }
+    }
+
+    pub fn color(r: f64, g: f64, b: f64) -> Self {
+        Isotropic {
+            albedo: Texture::ConstantTexture(ConstantTexture::from(r, g, b)),
+        }
+    }
+}
+
+impl Scatterable for Isotropic {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
+        let scattered = Ray::from(Point3::from_vec(rec.p), random_unit_vector(), ray.time);
+        let attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+
+        Some((scattered, attenuation))
+    }
+}
@@ -34,10 +34,53 @@ impl Lambertian {
 
 impl Scatterable for Lambertian {
     fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<(Ray, Vector3<f64>)> {
-        let target = rec.p + rec.normal + point_in_unit_sphere();
-        let scattered = Ray::from(Point3::from_vec(rec.p), target - rec.p, ray.time);
-        let attenuation = self.albedo.value(0.0, 0.0, rec.p);
+        let mut direction = rec.normal + point_in_unit_sphere();
+        // A shading normal can tilt the bounce into the surface, so it is mirrored back out
+        let below = direction.dot(rec.geometric_normal);
+        if below < 0.0 {
+            direction -= 2.0 * below * rec.geometric_normal;
+        }
+        let scattered = Ray::from(Point3::from_vec(rec.p), direction, ray.time);
+        let mut attenuation = self.albedo.value(rec.u, rec.v, rec.p);
+        if let Some(color) = rec.color {
+            attenuation = attenuation.mul_element_wise(color);
+        }
 
         Some((scattered, attenuation))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::Material;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::Hittable;
+
+    use cgmath::vec3;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn scatters_back_towards_the_ray() {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        let sphere = Sphere::from(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::clone(&material));
+
+        // From inside the sphere and from outside, with a shading normal leaning far over
+        let inside = Ray::from(Point3::new(0.0, 0.0, 0.0), vec3(0.6, 0.0, 0.8), 0.0);
+        let outside = Ray::from(Point3::new(0.0, 0.0, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        for ray in &[inside, outside] {
+            let mut rec = sphere.hits(ray, 0.001, f64::MAX).unwrap();
+            for &lean in &[0.0, 2.0] {
+                // The outward normal of the sphere tilted towards +x
+                rec.set_shading_normal((rec.p + vec3(lean, 0.0, 0.0)).normalize());
+                for _ in 0..1000 {
+                    let (scattered, attenuation) = material.scatter(ray, &rec).unwrap();
+                    assert!(scattered.direction.dot(ray.direction) < 0.0);
+                    assert!(scattered.direction.dot(rec.geometric_normal) >= 0.0);
+                    assert_eq!(attenuation, vec3(0.5, 0.5, 0.5));
+                }
+            }
+        }
+    }
+}
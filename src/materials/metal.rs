@@ -1,5 +1,8 @@
 use crate::objects::HitRecord;
 use crate::ray::Ray;
+use crate::textures::constant_texture::ConstantTexture;
+use crate::textures::Texture;
+use crate::textures::Textured;
 
 use super::{point_in_unit_sphere, Scatterable};
 
@@ -9,23 +12,23 @@ use cgmath::vec3;
 use cgmath::Point3;
 use cgmath::Vector3;
 
-#[derive(Debug, Clone, Copy)]
 pub struct Metal {
-    albedo: Vector3<f64>,
+    albedo: Texture,
     fuzz: f64,
 }
 
 impl Metal {
     pub fn new(albedo: Vector3<f64>, fuzz: f64) -> Self {
-        Metal {
-            albedo,
-            fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
-        }
+        Metal::textured(Texture::ConstantTexture(ConstantTexture::new(albedo)), fuzz)
     }
 
     pub fn from(x: f64, y: f64, z: f64, fuzz: f64) -> Self {
+        Metal::new(vec3::<f64>(x, y, z), fuzz)
+    }
+
+    pub fn textured(albedo: Texture, fuzz: f64) -> Self {
         Metal {
-            albedo: vec3::<f64>(x, y, z),
+            albedo,
             fuzz: if fuzz < 1.0 { fuzz } else { 1.0 },
         }
     }
@@ -39,9 +42,10 @@ impl Scatterable for Metal {
             reflected + point_in_unit_sphere() * self.fuzz,
             ray.time,
         );
-        let attenuation = self.albedo;
+        let attenuation = self.albedo.value(rec.u, rec.v, rec.p);
 
-        if dot(scattered.direction, rec.normal) > 0.0 {
+        // Shading normals and fuzz can both send the ray into the surface
+        if dot(scattered.direction, rec.geometric_normal) > 0.0 {
             Some((scattered, attenuation))
         } else {
             None
@@ -1,12 +1,20 @@
 use crate::objects::HitRecord;
 use crate::ray::Ray;
 
+pub mod conductor;
 pub mod dielectric;
+pub mod hair;
+pub mod henyey_greenstein;
+pub mod isotropic;
 pub mod lambertian;
 pub mod light;
 pub mod metal;
 
+use self::conductor::Conductor;
 use self::dielectric::Dielectric;
+use self::hair::Hair;
+use self::henyey_greenstein::HenyeyGreenstein;
+use self::isotropic::Isotropic;
 use self::lambertian::Lambertian;
 use self::light::DiffuseLight;
 use self::metal::Metal;
@@ -17,6 +25,8 @@ use cgmath::InnerSpace;
 use cgmath::Vector3;
 use rand::prelude::*;
 
+use std::f64::consts::PI;
+
 pub trait Scatterable {
     fn scatter(&self, ray_in: &Ray, rec: &HitRecord) -> Option<(Ray, Vector3<f64>)>;
     fn emitted(&self, _u: f64, _v: f64, _p: Vector3<f64>) -> Vector3<f64> {
@@ -27,8 +37,12 @@ pub trait Scatterable {
 pub enum Material {
     Lambertian(Lambertian),
     Metal(Metal),
+    Conductor(Conductor),
     Dielectric(Dielectric),
     DiffuseLight(DiffuseLight),
+    Isotropic(Isotropic),
+    HenyeyGreenstein(HenyeyGreenstein),
+    Hair(Hair),
 }
 
 impl Scatterable for Material {
@@ -36,8 +50,12 @@ impl Scatterable for Material {
         match *self {
             Material::Lambertian(ref inner) => inner.scatter(ray, rec),
             Material::Metal(ref inner) => inner.scatter(ray, rec),
+            Material::Conductor(ref inner) => inner.scatter(ray, rec),
             Material::Dielectric(ref inner) => inner.scatter(ray, rec),
             Material::DiffuseLight(ref inner) => inner.scatter(ray, rec),
+            Material::Isotropic(ref inner) => inner.scatter(ray, rec),
+            Material::HenyeyGreenstein(ref inner) => inner.scatter(ray, rec),
+            Material::Hair(ref inner) => inner.scatter(ray, rec),
         }
     }
 
@@ -45,8 +63,12 @@ impl Scatterable for Material {
         match *self {
             Material::Lambertian(ref inner) => inner.emitted(u, v, p),
             Material::Metal(ref inner) => inner.emitted(u, v, p),
+            Material::Conductor(ref inner) => inner.emitted(u, v, p),
             Material::Dielectric(ref inner) => inner.emitted(u, v, p),
             Material::DiffuseLight(ref inner) => inner.emitted(u, v, p),
+            Material::Isotropic(ref inner) => inner.emitted(u, v, p),
+            Material::HenyeyGreenstein(ref inner) => inner.emitted(u, v, p),
+            Material::Hair(ref inner) => inner.emitted(u, v, p),
         }
     }
 }
@@ -60,6 +82,14 @@ fn point_in_unit_sphere() -> Vector3<f64> {
     .normalize()
 }
 
+// Uniformly distributed over the sphere of directions
+fn random_unit_vector() -> Vector3<f64> {
+    let z = 1.0 - 2.0 * random::<f64>();
+    let r = (1.0 - z * z).max(0.0).sqrt();
+    let phi = 2.0 * PI * random::<f64>();
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
 fn reflect(v: Vector3<f64>, n: Vector3<f64>) -> Vector3<f64> {
     v - 2.0 * dot(v, n) * n
 }
@@ -0,0 +1,422 @@
+use super::transform::transform_box;
+use super::transform::transform_hit;
+use super::transform::transform_ray;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Matrix4;
+use cgmath::Quaternion;
+use cgmath::Vector3;
+
+use std::f64;
+
+// The time between two keyframes is split into this many steps when bounding the motion. More
+// steps give a tighter box.
+const BOUND_STEPS: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    // A cubic Bézier from (0, 0) to (1, 1) with the control points (x1, y1) and (x2, y2), like
+    // CSS `cubic-bezier`. Control points are clamped to [0, 1], so the value never overshoots
+    // the keyframes.
+    Bezier(f64, f64, f64, f64),
+}
+
+impl Easing {
+    pub fn ease_in() -> Self {
+        Easing::Bezier(0.42, 0.0, 1.0, 1.0)
+    }
+
+    pub fn ease_out() -> Self {
+        Easing::Bezier(0.0, 0.0, 0.58, 1.0)
+    }
+
+    pub fn ease_in_out() -> Self {
+        Easing::Bezier(0.42, 0.0, 0.58, 1.0)
+    }
+
+    // Maps the fraction of the time between two keyframes to the fraction of the way between
+    // their values
+    pub fn apply(self, s: f64) -> f64 {
+        match self {
+            Easing::Linear => s,
+            Easing::Bezier(x1, y1, x2, y2) => {
+                let bezier = |p1: f64, p2: f64, u: f64| {
+                    let (p1, p2) = (p1.clamp(0.0, 1.0), p2.clamp(0.0, 1.0));
+                    let v = 1.0 - u;
+                    3.0 * v * v * u * p1 + 3.0 * v * u * u * p2 + u * u * u
+                };
+
+                // x grows with u when its control points are in [0, 1]
+                let (mut lo, mut hi) = (0.0, 1.0);
+                for _ in 0..40 {
+                    let middle = 0.5 * (lo + hi);
+                    if bezier(x1, x2, middle) < s {
+                        lo = middle;
+                    } else {
+                        hi = middle;
+                    }
+                }
+                bezier(y1, y2, 0.5 * (lo + hi))
+            }
+        }
+    }
+}
+
+pub trait Interpolate: Copy {
+    fn interpolate(self, other: Self, s: f64) -> Self;
+
+    // The form keyframe values are kept in
+    fn canonical(self) -> Self {
+        self
+    }
+}
+
+impl Interpolate for Vector3<f64> {
+    fn interpolate(self, other: Self, s: f64) -> Self {
+        self.lerp(other, s)
+    }
+}
+
+impl Interpolate for Quaternion<f64> {
+    // Only unit quaternions are rotations
+    fn canonical(self) -> Self {
+        self.normalize()
+    }
+
+    // Turns the shorter way around
+    fn interpolate(self, other: Self, s: f64) -> Self {
+        let other = if self.dot(other) < 0.0 { -other } else { other };
+        self.slerp(other, s).normalize()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Keyframe<T> {
+    pub time: f64,
+    pub value: T,
+    // How the value moves on to the next keyframe
+    pub easing: Easing,
+}
+
+impl<T> Keyframe<T> {
+    pub fn new(time: f64, value: T, easing: Easing) -> Self {
+        Keyframe {
+            time,
+            value,
+            easing,
+        }
+    }
+}
+
+// A value changing over time. It holds the first value before the first keyframe and the last
+// value after the last one.
+#[derive(Debug, Clone)]
+pub struct Track<T> {
+    keyframes: Vec<Keyframe<T>>,
+}
+
+impl<T: Interpolate> Track<T> {
+    pub fn new(mut keyframes: Vec<Keyframe<T>>) -> Self {
+        assert!(!keyframes.is_empty(), "Track needs at least one keyframe");
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        for key in &mut keyframes {
+            key.value = key.value.canonical();
+        }
+
+        Track { keyframes }
+    }
+
+    pub fn constant(value: T) -> Self {
+        Track::new(vec![Keyframe::new(0.0, value, Easing::Linear)])
+    }
+
+    pub fn linear(time0: f64, value0: T, time1: f64, value1: T) -> Self {
+        Track::new(vec![
+            Keyframe::new(time0, value0, Easing::Linear),
+            Keyframe::new(time1, value1, Easing::Linear),
+        ])
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe<T>] {
+        &self.keyframes
+    }
+
+    pub fn sample(&self, time: f64) -> T {
+        let keyframes = &self.keyframes;
+        let next = keyframes.partition_point(|key| key.time <= time);
+        if next == 0 {
+            return keyframes[0].value;
+        }
+        if next == keyframes.len() {
+            return keyframes[next - 1].value;
+        }
+
+        let (a, b) = (&keyframes[next - 1], &keyframes[next]);
+        let s = (time - a.time) / (b.time - a.time);
+        a.value.interpolate(b.value, a.easing.apply(s))
+    }
+
+    // `t0`, `t1` and the keyframes in between. Between two of these times the value only moves
+    // one way along the line, or arc, between two keyframes.
+    pub fn times(&self, t0: f64, t1: f64) -> Vec<f64> {
+        let mut times = vec![t0];
+        times.extend(
+            self.keyframes
+                .iter()
+                .map(|key| key.time)
+                .filter(|&time| time > t0 && time < t1),
+        );
+        if t1 > t0 {
+            times.push(t1);
+        }
+        times.dedup();
+
+        times
+    }
+}
+
+// Scales, then rotates, then translates an object, each along its own track
+#[derive(Debug, Clone)]
+pub struct Animation {
+    pub translation: Track<Vector3<f64>>,
+    pub rotation: Track<Quaternion<f64>>,
+    pub scale: Track<Vector3<f64>>,
+}
+
+impl Animation {
+    pub fn new(
+        translation: Track<Vector3<f64>>,
+        rotation: Track<Quaternion<f64>>,
+        scale: Track<Vector3<f64>>,
+    ) -> Self {
+        Animation {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn matrix(&self, time: f64) -> Matrix4<f64> {
+        let scale = self.scale.sample(time);
+        Matrix4::from_translation(self.translation.sample(time))
+            * Matrix4::from(self.rotation.sample(time))
+            * Matrix4::from_nonuniform_scale(scale.x, scale.y, scale.z)
+    }
+
+    fn times(&self, t0: f64, t1: f64) -> Vec<f64> {
+        let mut times = self.translation.times(t0, t1);
+        times.extend(self.rotation.times(t0, t1));
+        times.extend(self.scale.times(t0, t1));
+        times.sort_by(|a, b| a.total_cmp(b));
+        times.dedup();
+
+        times
+    }
+}
+
+// Moves a hittable along an animation. Every ray meets the object where it is at the time of
+// the ray, so it blurs along its motion over the shutter interval.
+pub struct Animated<T: Hittable> {
+    pub hittable: T,
+    pub animation: Animation,
+}
+
+impl<T: Hittable> Animated<T> {
+    pub fn new(hittable: T, animation: Animation) -> Self {
+        Animated {
+            hittable,
+            animation,
+        }
+    }
+}
+
+impl<T: Hittable> Hittable for Animated<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let matrix = self.animation.matrix(ray.time);
+        // A zero scale squashes the object flat, which no ray can hit
+        let inverse = matrix.invert()?;
+
+        let hit = self
+            .hittable
+            .hits(&transform_ray(&inverse, ray), t_min, t_max)?;
+        Some(transform_hit(&matrix, &inverse.transpose(), hit))
+    }
+
+    // Covers the object over the shutter interval, split into steps that each lie between two
+    // keyframes of every track. Easing never overshoots, so over a step the translation stays
+    // on the line between its values at either end, each scale factor stays between its end
+    // values, and the rotation turns about one axis by at most the angle between its ends. A
+    // corner p, scaled to v = S·p, then stays within |v|·sin(angle / 2) of the middle of the
+    // chord its rotation sweeps, plus however far the scaling moves it.
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        let bx = self.hittable.bounding_box(t0, t1)?;
+        let keys = self.animation.times(t0, t1);
+        if keys.len() < 2 {
+            return Some(transform_box(&self.animation.matrix(keys[0]), &bx));
+        }
+
+        let corners: Vec<Vector3<f64>> = (0..8)
+            .map(|i| {
+                vec3(
+                    if i & 1 == 0 { bx.min.x } else { bx.max.x },
+                    if i & 2 == 0 { bx.min.y } else { bx.max.y },
+                    if i & 4 == 0 { bx.min.z } else { bx.max.z },
+                )
+            })
+            .collect();
+
+        let mut times = vec![keys[0]];
+        for pair in keys.windows(2) {
+            for step in 1..=BOUND_STEPS {
+                times.push(pair[0] + (pair[1] - pair[0]) * step as f64 / BOUND_STEPS as f64);
+            }
+        }
+
+        let animation = &self.animation;
+        let mut min = vec3(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut max = vec3(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+        for pair in times.windows(2) {
+            let (t_a, t_b) = (pair[0], pair[1]);
+            let (translation_a, translation_b) = (
+                animation.translation.sample(t_a),
+                animation.translation.sample(t_b),
+            );
+            let (rotation_a, rotation_b) = (
+                animation.rotation.sample(t_a),
+                animation.rotation.sample(t_b),
+            );
+            let (scale_a, scale_b) = (animation.scale.sample(t_a), animation.scale.sample(t_b));
+            // |q_a·q_b| is the cosine of half the angle between the two rotations
+            let sin_half_angle = (1.0 - rotation_a.dot(rotation_b).powi(2)).max(0.0).sqrt();
+
+            for &corner in &corners {
+                let v_a = scale_a.mul_element_wise(corner);
+                let v_b = scale_b.mul_element_wise(corner);
+                let middle = 0.5 * (rotation_a.rotate_vector(v_a) + rotation_b.rotate_vector(v_a));
+                let pad = v_a.magnitude() * sin_half_angle + (v_b - v_a).magnitude();
+                for a in 0..3 {
+                    min[a] = min[a].min(translation_a[a].min(translation_b[a]) + middle[a] - pad);
+                    max[a] = max[a].max(translation_a[a].max(translation_b[a]) + middle[a] + pad);
+                }
+            }
+        }
+
+        Some(AABB::new(min, max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::materials::Material;
+    use crate::objects::moving_sphere::MovingSphere;
+    use crate::objects::sphere::Sphere;
+
+    use cgmath::Deg;
+    use cgmath::Point3;
+
+    use std::sync::Arc;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn moves_linearly_between_keyframes() {
+        let sphere = MovingSphere::from(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(4.0, -2.0, 8.0),
+            2.0,
+            4.0,
+            1.0,
+            material(),
+        );
+        assert_eq!(sphere.center(3.0), vec3(2.0, -1.0, 4.0));
+        assert_eq!(sphere.center(2.5), vec3(1.0, -0.5, 2.0));
+        assert_eq!(sphere.center(1.0), vec3(0.0, 0.0, 0.0));
+        assert_eq!(sphere.center(5.0), vec3(4.0, -2.0, 8.0));
+
+        let track = Track::new(vec![
+            Keyframe::new(3.0, 5.0 * Vector3::unit_x(), Easing::Linear),
+            Keyframe::new(1.0, Vector3::zero(), Easing::Linear),
+            Keyframe::new(2.0, Vector3::unit_x(), Easing::Linear),
+        ]);
+        assert_eq!(track.sample(1.5), 0.5 * Vector3::unit_x());
+        assert_eq!(track.sample(2.5), 3.0 * Vector3::unit_x());
+    }
+
+    #[test]
+    fn eases_monotonically() {
+        let easings = [
+            Easing::Linear,
+            Easing::ease_in(),
+            Easing::ease_out(),
+            Easing::ease_in_out(),
+            Easing::Bezier(0.9, -0.5, 0.1, 1.5),
+        ];
+        for &easing in &easings {
+            assert!(easing.apply(0.0).abs() < 1e-9);
+            assert!((easing.apply(1.0) - 1.0).abs() < 1e-9);
+
+            let mut previous = 0.0;
+            for i in 0..=1000 {
+                let value = easing.apply(f64::from(i) / 1000.0);
+                assert!(value >= previous - 1e-12 && value <= 1.0 + 1e-12);
+                previous = value;
+            }
+        }
+    }
+
+    #[test]
+    fn bounds_the_object_over_the_shutter() {
+        let translation = Track::new(vec![
+            Keyframe::new(0.0, vec3(0.0, 0.0, 0.0), Easing::ease_in_out()),
+            Keyframe::new(0.6, vec3(3.0, 1.0, 0.0), Easing::ease_out()),
+            Keyframe::new(1.0, vec3(2.0, 4.0, -1.0), Easing::Linear),
+        ]);
+        let rotation = Track::new(vec![
+            Keyframe::new(0.0, Quaternion::one(), Easing::ease_in()),
+            Keyframe::new(
+                0.5,
+                Quaternion::from_axis_angle(vec3(1.0, 1.0, 0.0).normalize(), Deg(170.0)),
+                Easing::Linear,
+            ),
+            Keyframe::new(
+                1.0,
+                // Not normalized, which the track does itself
+                Quaternion::from_axis_angle(Vector3::unit_z(), Deg(-120.0)) * 2.0,
+                Easing::Linear,
+            ),
+        ]);
+        let scale = Track::linear(0.2, vec3(1.0, 1.0, 1.0), 0.8, vec3(3.0, 0.5, 1.0));
+        let animation = Animation::new(translation, rotation, scale);
+        let center = Point3::new(1.0, 0.5, -0.5);
+        let animated = Animated::new(Sphere::from(center, 0.5, material()), animation);
+
+        let (t0, t1) = (0.1, 0.9);
+        let bx = animated.bounding_box(t0, t1).unwrap();
+        for i in 0..=200 {
+            let time = t0 + (t1 - t0) * f64::from(i) / 200.0;
+            let matrix = animated.animation.matrix(time);
+            for j in 0..100 {
+                let (theta, phi) = (f64::from(j % 10) * 0.35, f64::from(j / 10) * 0.63);
+                let offset = vec3(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                let p = matrix.transform_point(center + 0.5 * offset);
+                for a in 0..3 {
+                    assert!(bx.min[a] <= p[a] + 1e-9 && p[a] <= bx.max[a] + 1e-9);
+                }
+            }
+        }
+    }
+}
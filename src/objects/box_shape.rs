@@ -0,0 +1,91 @@
+use super::rect::FlipNormals;
+use super::rect::XYRect;
+use super::rect::XZRect;
+use super::rect::YZRect;
+use super::HitRecord;
+use super::Hittable;
+use super::HittableList;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::Point3;
+
+use std::sync::Arc;
+
+pub struct BoxShape {
+    pub min: Point3<f64>,
+    pub max: Point3<f64>,
+    sides: HittableList,
+}
+
+impl BoxShape {
+    pub fn from(p0: Point3<f64>, p1: Point3<f64>, material: Arc<Material>) -> Self {
+        let min = Point3::new(p0.x.min(p1.x), p0.y.min(p1.y), p0.z.min(p1.z));
+        let max = Point3::new(p0.x.max(p1.x), p0.y.max(p1.y), p0.z.max(p1.z));
+        let m = || Arc::clone(&material);
+
+        let mut sides = HittableList::new();
+        sides.add(Box::new(XYRect::new(
+            min.x,
+            max.x,
+            min.y,
+            max.y,
+            max.z,
+            m(),
+        )));
+        sides.add(Box::new(FlipNormals::new(XYRect::new(
+            min.x,
+            max.x,
+            min.y,
+            max.y,
+            min.z,
+            m(),
+        ))));
+        sides.add(Box::new(XZRect::new(
+            min.x,
+            max.x,
+            min.z,
+            max.z,
+            max.y,
+            m(),
+        )));
+        sides.add(Box::new(FlipNormals::new(XZRect::new(
+            min.x,
+            max.x,
+            min.z,
+            max.z,
+            min.y,
+            m(),
+        ))));
+        sides.add(Box::new(YZRect::new(
+            min.y,
+            max.y,
+            min.z,
+            max.z,
+            max.x,
+            m(),
+        )));
+        sides.add(Box::new(FlipNormals::new(YZRect::new(
+            min.y,
+            max.y,
+            min.z,
+            max.z,
+            min.x,
+            m(),
+        ))));
+
+        BoxShape { min, max, sides }
+    }
+}
+
+impl Hittable for BoxShape {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.sides.hits(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(self.min.to_vec(), self.max.to_vec()).pad(0.0001))
+    }
+}
@@ -60,4 +60,10 @@ impl Camera {
             time,
         )
     }
+
+    // Height of a pixel at `distance` from the camera in an image `height` pixels tall
+    pub fn pixel_size(&self, distance: f64, height: u16) -> f64 {
+        let focus_dist = (self.origin - self.lower_left_corner).dot(self.w);
+        distance * self.vertical.magnitude() / (focus_dist * f64::from(height))
+    }
 }
@@ -0,0 +1,170 @@
+use super::disk::disk_uv;
+use super::disk::hit_disk;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+
+use std::sync::Arc;
+
+// A cone with a base disk of `radius` around `base` and its apex `height` above it along +y.
+// Capped cones are closed by the base disk.
+pub struct Cone {
+    pub base: Point3<f64>,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    material: Arc<Material>,
+}
+
+impl Cone {
+    pub fn new(base: Point3<f64>, radius: f64, height: f64, material: Arc<Material>) -> Self {
+        Cone {
+            base,
+            radius,
+            height,
+            capped: false,
+            material,
+        }
+    }
+
+    pub fn capped(base: Point3<f64>, radius: f64, height: f64, material: Arc<Material>) -> Self {
+        Cone {
+            capped: true,
+            ..Cone::new(base, radius, height, material)
+        }
+    }
+}
+
+impl Hittable for Cone {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Solves x² + z² = k (y - height)² with the origin moved to the center of the base
+        let k = (self.radius / self.height).powi(2);
+        let oc = ray.origin - self.base;
+        let d = ray.direction;
+        let oy = oc.y - self.height;
+        let a = d.x * d.x + d.z * d.z - k * d.y * d.y;
+        let b = 2.0 * (oc.x * d.x + oc.z * d.z - k * oy * d.y);
+        let c = oc.x * oc.x + oc.z * oc.z - k * oy * oy;
+
+        // Missing roots are left as NaN, which fails every comparison below
+        let roots = if a.abs() < 1e-12 {
+            // The ray is parallel to the slope of the cone and crosses it at most once
+            [-c / b, f64::NAN]
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                [f64::NAN, f64::NAN]
+            } else {
+                let root = discriminant.sqrt();
+                let (t0, t1) = ((-b - root) / (2.0 * a), (-b + root) / (2.0 * a));
+                [t0.min(t1), t0.max(t1)]
+            }
+        };
+
+        let mut closest = t_max;
+        let mut hit = None;
+
+        for &t in &roots {
+            let y = oc.y + t * d.y;
+            if t > t_min && t < closest && y >= 0.0 && y <= self.height {
+                let point = ray.point_at(t);
+                let offset = point - self.base.to_vec();
+                let normal = vec3(offset.x, k * (self.height - y), offset.z).normalize();
+                let u = super::get_angle_uv(offset.x, offset.z);
+                let v = y / self.height;
+                let material = Arc::clone(&self.material);
+                closest = t;
+                hit = Some(HitRecord::new(ray, t, point, normal, material, u, v));
+                break;
+            }
+        }
+
+        if self.capped {
+            if let Some((t, point)) = hit_disk(self.base, 0.0, self.radius, ray, t_min, closest) {
+                let (u, v) = disk_uv(point - self.base, 0.0, self.radius);
+                let normal = vec3(0.0, -1.0, 0.0);
+                let material = Arc::clone(&self.material);
+                hit = Some(HitRecord::new(
+                    ray,
+                    t,
+                    point.to_vec(),
+                    normal,
+                    material,
+                    u,
+                    v,
+                ));
+            }
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(
+            self.base.to_vec() - vec3(self.radius, 0.0, self.radius),
+            self.base.to_vec() + vec3(self.radius, self.height, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn hits_the_slope() {
+        let cone = Cone::new(Point3::new(0.0, 0.0, 0.0), 1.0, 2.0, material());
+
+        // Halfway up the radius is halved, and the normal leans up by the slope
+        let ray = Ray::from(Point3::new(-3.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = cone.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 2.5);
+        assert!((hit.normal - vec3(-2.0, 1.0, 0.0).normalize()).magnitude() < 1e-12);
+        assert_eq!(hit.v, 0.5);
+    }
+
+    #[test]
+    fn misses_around_the_apex() {
+        let cone = Cone::new(Point3::new(0.0, 0.0, 0.0), 1.0, 2.0, material());
+
+        let over = Ray::from(Point3::new(-3.0, 2.1, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(cone.hits(&over, 0.001, f64::MAX).is_none());
+        let beside = Ray::from(Point3::new(-3.0, 1.0, 0.6), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(cone.hits(&beside, 0.001, f64::MAX).is_none());
+        // The other nappe of the double cone above the apex is not part of it
+        let mirrored = Ray::from(Point3::new(-3.0, 3.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(cone.hits(&mirrored, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn hits_the_base() {
+        let up = Ray::from(Point3::new(0.3, -1.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+
+        let cone = Cone::capped(Point3::new(0.0, 0.0, 0.0), 1.0, 2.0, material());
+        let hit = cone.hits(&up, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.normal, vec3(0.0, -1.0, 0.0));
+        assert!(hit.front_face);
+
+        // Without its base the cone is seen from the inside
+        let cone = Cone::new(Point3::new(0.0, 0.0, 0.0), 1.0, 2.0, material());
+        let hit = cone.hits(&up, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 2.4).abs() < 1e-12);
+        assert!(!hit.front_face);
+
+        let bx = cone.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!(bx.min, vec3(-1.0, 0.0, -1.0));
+        assert_eq!(bx.max, vec3(1.0, 2.0, 1.0));
+    }
+}
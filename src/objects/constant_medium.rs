@@ -0,0 +1,114 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::isotropic::Isotropic;
+use crate::materials::Material;
+use crate::ray::Ray;
+use crate::textures::Texture;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use rand::prelude::*;
+
+use std::f64;
+use std::sync::Arc;
+
+// A volume of constant density filling a closed boundary, such as smoke or fog
+pub struct ConstantMedium<T: Hittable> {
+    pub boundary: T,
+    neg_inv_density: f64,
+    phase_function: Arc<Material>,
+}
+
+impl<T: Hittable> ConstantMedium<T> {
+    pub fn new(boundary: T, density: f64, albedo: Texture) -> Self {
+        ConstantMedium {
+            boundary,
+            neg_inv_density: -1.0 / density,
+            phase_function: Arc::new(Material::Isotropic(Isotropic::new(albedo))),
+        }
+    }
+}
+
+impl<T: Hittable> Hittable for ConstantMedium<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        // Find where the ray enters and leaves the boundary, even when it starts inside
+        let enter = self.boundary.hits(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hits(ray, enter.t + 0.0001, f64::INFINITY)?;
+
+        let t_enter = enter.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_enter >= t_exit {
+            return None;
+        }
+
+        let ray_length = ray.direction.magnitude();
+        let distance_inside = (t_exit - t_enter) * ray_length;
+        let hit_distance = self.neg_inv_density * thread_rng().gen::<f64>().ln();
+        if hit_distance > distance_inside {
+            return None;
+        }
+
+        let t = t_enter + hit_distance / ray_length;
+        let point = ray.point_at(t);
+        // The normal is meaningless inside a volume
+        let normal = vec3(1.0, 0.0, 0.0);
+        let material = Arc::clone(&self.phase_function);
+        Some(HitRecord::new(ray, t, point, normal, material, 0.0, 0.0))
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.boundary.bounding_box(t0, t1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::objects::sphere::Sphere;
+    use crate::textures::constant_texture::ConstantTexture;
+
+    use cgmath::Point3;
+
+    fn fog(density: f64) -> ConstantMedium<Sphere> {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        let boundary = Sphere::from(Point3::new(0.0, 0.0, 0.0), 1.0, material);
+        let albedo = Texture::ConstantTexture(ConstantTexture::new(vec3(0.8, 0.8, 0.8)));
+        ConstantMedium::new(boundary, density, albedo)
+    }
+
+    #[test]
+    fn scatters_inside_the_boundary() {
+        let fog = fog(2.0);
+        let ray = Ray::from(Point3::new(0.0, 0.0, -3.0), vec3(0.0, 0.0, 2.0), 0.0);
+        for _ in 0..1000 {
+            if let Some(hit) = fog.hits(&ray, 0.001, f64::MAX) {
+                assert!(hit.t >= 1.0 && hit.t <= 2.0);
+                assert!(matches!(*hit.material, Material::Isotropic(_)));
+            }
+        }
+
+        // Rays starting inside only scatter ahead of their origin
+        let ray = Ray::from(Point3::new(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), 0.0);
+        for _ in 0..1000 {
+            if let Some(hit) = fog.hits(&ray, 0.001, f64::MAX) {
+                assert!(hit.t >= 0.001 && hit.t <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn passes_through_empty_and_stops_in_dense_fog() {
+        let ray = Ray::from(Point3::new(0.0, 0.0, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let empty = fog(0.0);
+        assert!((0..1000).all(|_| empty.hits(&ray, 0.001, f64::MAX).is_none()));
+
+        let dense = fog(1e9);
+        let hit = dense.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 2.0).abs() < 1e-6);
+
+        let beside = Ray::from(Point3::new(0.0, 1.5, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!(dense.hits(&beside, 0.001, f64::MAX).is_none());
+    }
+}
@@ -0,0 +1,261 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::ray::Ray;
+
+use cgmath::vec3;
+
+use std::f64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CsgOperation {
+    Union,
+    Intersection,
+    // Removes the second object from the first
+    Difference,
+}
+
+impl CsgOperation {
+    fn inside(self, a: bool, b: bool) -> bool {
+        match self {
+            CsgOperation::Union => a || b,
+            CsgOperation::Intersection => a && b,
+            CsgOperation::Difference => a && !b,
+        }
+    }
+}
+
+// Combines two closed objects with a boolean operation. Both objects need normals pointing out
+// of their volume, which is how each crossing is told to be an entry or an exit.
+pub struct Csg<A: Hittable, B: Hittable> {
+    pub a: A,
+    pub b: B,
+    pub operation: CsgOperation,
+}
+
+impl<A: Hittable, B: Hittable> Csg<A, B> {
+    pub fn new(a: A, b: B, operation: CsgOperation) -> Self {
+        Csg { a, b, operation }
+    }
+
+    pub fn union(a: A, b: B) -> Self {
+        Csg::new(a, b, CsgOperation::Union)
+    }
+
+    pub fn intersection(a: A, b: B) -> Self {
+        Csg::new(a, b, CsgOperation::Intersection)
+    }
+
+    pub fn difference(a: A, b: B) -> Self {
+        Csg::new(a, b, CsgOperation::Difference)
+    }
+}
+
+impl<A: Hittable, B: Hittable> Hittable for Csg<A, B> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.crossings(ray, t_min, t_max).into_iter().next()
+    }
+
+    // Walks the crossings of both objects in order along the ray, keeping the ones that change
+    // whether the ray is inside the combined volume
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let crossings_a = self.a.crossings(ray, t_min, t_max);
+        let crossings_b = self.b.crossings(ray, t_min, t_max);
+        let mut inside_a = starts_inside(&self.a, &crossings_a, ray, t_max);
+        let mut inside_b = starts_inside(&self.b, &crossings_b, ray, t_max);
+
+        let mut crossings = Vec::new();
+        let mut crossings_a = crossings_a.into_iter().peekable();
+        let mut crossings_b = crossings_b.into_iter().peekable();
+        loop {
+            let inside = self.operation.inside(inside_a, inside_b);
+            let from_a = match (crossings_a.peek(), crossings_b.peek()) {
+                (Some(a), Some(b)) => a.t <= b.t,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => return crossings,
+            };
+
+            let mut hit = if from_a {
+                inside_a = !inside_a;
+                crossings_a.next().unwrap()
+            } else {
+                inside_b = !inside_b;
+                crossings_b.next().unwrap()
+            };
+
+            if self.operation.inside(inside_a, inside_b) != inside {
+                // The inside of a subtracted object becomes the outside of the result
+                if !from_a && self.operation == CsgOperation::Difference {
+                    hit.front_face = !hit.front_face;
+                }
+                crossings.push(hit);
+            }
+        }
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        match self.operation {
+            CsgOperation::Union => {
+                let a = self.a.bounding_box(t0, t1)?;
+                let b = self.b.bounding_box(t0, t1)?;
+                Some(a.surrounding_box(&b))
+            }
+            CsgOperation::Intersection => {
+                match (self.a.bounding_box(t0, t1), self.b.bounding_box(t0, t1)) {
+                    (Some(a), Some(b)) => {
+                        // Disjoint boxes leave an empty box which no ray hits
+                        let min = vec3(
+                            a.min.x.max(b.min.x),
+                            a.min.y.max(b.min.y),
+                            a.min.z.max(b.min.z),
+                        );
+                        let max = vec3(
+                            a.max.x.min(b.max.x),
+                            a.max.y.min(b.max.y),
+                            a.max.z.min(b.max.z),
+                        );
+                        Some(AABB::new(min, max))
+                    }
+                    (a, b) => a.or(b),
+                }
+            }
+            CsgOperation::Difference => self.a.bounding_box(t0, t1),
+        }
+    }
+}
+
+// Whether the ray starts inside a closed object, which follows from its first crossing being
+// an exit. Only when there is no crossing before t_max does this look further along the ray.
+fn starts_inside<T: Hittable>(object: &T, crossings: &[HitRecord], ray: &Ray, t_max: f64) -> bool {
+    match crossings.first() {
+        Some(hit) => !hit.front_face,
+        None => matches!(object.hits(ray, t_max, f64::INFINITY), Some(hit) if !hit.front_face),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::materials::Material;
+    use crate::objects::sphere::Sphere;
+
+    use cgmath::prelude::*;
+    use cgmath::Point3;
+    use cgmath::Vector3;
+
+    use std::sync::Arc;
+
+    // Unit spheres around the origin and around (1, 0, 0), overlapping for 0 <= x <= 1
+    fn spheres() -> (Sphere, Sphere) {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        (
+            Sphere::from(Point3::new(0.0, 0.0, 0.0), 1.0, Arc::clone(&material)),
+            Sphere::from(Point3::new(1.0, 0.0, 0.0), 1.0, material),
+        )
+    }
+
+    fn ray(x: f64, direction: f64) -> Ray {
+        Ray::from(Point3::new(x, 0.0, 0.0), vec3(direction, 0.0, 0.0), 0.0)
+    }
+
+    fn summary(crossings: &[HitRecord]) -> Vec<(f64, Vector3<f64>, bool)> {
+        crossings
+            .iter()
+            .map(|hit| (hit.t, hit.normal, hit.front_face))
+            .collect()
+    }
+
+    #[test]
+    fn unites_overlapping_spheres() {
+        let (a, b) = spheres();
+        let union = Csg::union(a, b);
+
+        let ray = ray(-5.0, 1.0);
+        let crossings = union.crossings(&ray, 0.001, f64::MAX);
+        assert_eq!(
+            summary(&crossings),
+            vec![
+                (4.0, vec3(-1.0, 0.0, 0.0), true),
+                (7.0, vec3(-1.0, 0.0, 0.0), false),
+            ]
+        );
+        assert_eq!(union.hits(&ray, 0.001, f64::MAX).unwrap().t, 4.0);
+
+        let bx = union.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!((bx.min.x, bx.max.x), (-1.0, 2.0));
+    }
+
+    #[test]
+    fn intersects_into_a_lens() {
+        let (a, b) = spheres();
+        let lens = Csg::intersection(a, b);
+
+        let hit = lens.hits(&ray(-5.0, 1.0), 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 5.0);
+        assert_eq!(hit.normal, vec3(-1.0, 0.0, 0.0));
+        assert!(hit.front_face);
+
+        let hit = lens.hits(&ray(5.0, -1.0), 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert_eq!(hit.normal, vec3(1.0, 0.0, 0.0));
+
+        // Above the lens, but through both spheres
+        let above = Ray::from(Point3::new(-5.0, 0.9, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(lens.hits(&above, 0.001, f64::MAX).is_none());
+
+        let bx = lens.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!((bx.min.x, bx.max.x), (0.0, 1.0));
+    }
+
+    #[test]
+    fn flips_the_subtracted_surface() {
+        let (a, b) = spheres();
+        let difference = Csg::difference(a, b);
+
+        // From +x the ray passes through b first and meets what is left of a where b ends
+        let ray = ray(3.0, -1.0);
+        let crossings = difference.crossings(&ray, 0.001, f64::MAX);
+        assert_eq!(
+            summary(&crossings),
+            vec![
+                (3.0, vec3(1.0, 0.0, 0.0), true),
+                (4.0, vec3(1.0, 0.0, 0.0), false),
+            ]
+        );
+        assert!(crossings[0].normal.dot(ray.direction) < 0.0);
+
+        let hit = difference.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 3.0);
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn starts_inside_the_first_object() {
+        let (a, b) = spheres();
+        let union = Csg::union(a, b);
+        let crossings = union.crossings(&ray(-0.5, 1.0), 0.001, f64::MAX);
+        assert_eq!(
+            summary(&crossings),
+            vec![(2.5, vec3(-1.0, 0.0, 0.0), false)]
+        );
+
+        let (a, b) = spheres();
+        let difference = Csg::difference(a, b);
+        let crossings = difference.crossings(&ray(-0.5, 1.0), 0.001, f64::MAX);
+        assert_eq!(
+            summary(&crossings),
+            vec![(0.5, vec3(-1.0, 0.0, 0.0), false)]
+        );
+
+        // Inside both, with no crossing before t_max, the ray is still inside the lens
+        let (a, b) = spheres();
+        let lens = Csg::intersection(a, b);
+        let ray = ray(0.5, 1.0);
+        assert!(lens.hits(&ray, 0.001, 0.25).is_none());
+        let hit = lens.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 0.5);
+        assert!(!hit.front_face);
+    }
+}
@@ -0,0 +1,401 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::bvh::BvhTree;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::f64;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CurveType {
+    // A flat strip that always faces the incoming ray
+    Flat,
+    // A strip shaded as if it were a round tube, which suits hair and fur
+    Tube,
+}
+
+// A cubic Bézier curve swept with a width that varies linearly from `widths[0]` at the start
+// to `widths[1]` at the end. Curves are meant to be thin compared to their length.
+// Refer: http://www.pbr-book.org/3ed-2018/Shapes/Curves.html
+pub struct Curve {
+    pub points: [Point3<f64>; 4],
+    pub widths: [f64; 2],
+    pub kind: CurveType,
+    material: Arc<Material>,
+}
+
+impl Curve {
+    pub fn new(
+        points: [Point3<f64>; 4],
+        widths: [f64; 2],
+        kind: CurveType,
+        material: Arc<Material>,
+    ) -> Self {
+        Curve {
+            points,
+            widths,
+            kind,
+            material,
+        }
+    }
+
+    // Splits a strand given as a piecewise cubic Bézier with 3n + 1 control points into its n
+    // curves. The width tapers linearly from `widths[0]` at the root to `widths[1]` at the tip.
+    pub fn strand(
+        points: &[Point3<f64>],
+        widths: [f64; 2],
+        kind: CurveType,
+        material: Arc<Material>,
+    ) -> Vec<Curve> {
+        assert!(
+            points.len() >= 4 && points.len() % 3 == 1,
+            "A strand needs 3n + 1 control points"
+        );
+
+        let segments = (points.len() - 1) / 3;
+        let width = |s: usize| lerp(s as f64 / segments as f64, widths[0], widths[1]);
+        (0..segments)
+            .map(|s| {
+                let p = &points[3 * s..3 * s + 4];
+                Curve::new(
+                    [p[0], p[1], p[2], p[3]],
+                    [width(s), width(s + 1)],
+                    kind,
+                    Arc::clone(&material),
+                )
+            })
+            .collect()
+    }
+
+    fn width(&self, u: f64) -> f64 {
+        lerp(u, self.widths[0], self.widths[1])
+    }
+
+    // Tests the part of the curve between u0 and u1, given by the control points `cp` in a
+    // space where the ray starts at the origin and runs along +z. Returns the distance along
+    // the ray and u of the closest hit in (z_min, z_max).
+    fn intersect(
+        &self,
+        cp: &[Vector3<f64>; 4],
+        u0: f64,
+        u1: f64,
+        depth: i32,
+        z_min: f64,
+        z_max: f64,
+    ) -> Option<(f64, f64)> {
+        let half_width = 0.5 * self.width(u0).max(self.width(u1));
+        let (mut min, mut max) = (cp[0], cp[0]);
+        for p in &cp[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+        if min.x - half_width > 0.0
+            || max.x + half_width < 0.0
+            || min.y - half_width > 0.0
+            || max.y + half_width < 0.0
+            || min.z - half_width > z_max
+            || max.z + half_width < z_min
+        {
+            return None;
+        }
+
+        if depth > 0 {
+            let [first, second] = subdivide(cp);
+            let middle = 0.5 * (u0 + u1);
+            let hit = self.intersect(&first, u0, middle, depth - 1, z_min, z_max);
+            let z_max = hit.map_or(z_max, |(z, _)| z);
+            return self
+                .intersect(&second, middle, u1, depth - 1, z_min, z_max)
+                .or(hit);
+        }
+
+        // The ray has to pass between the perpendiculars to the curve at both ends
+        let edge = (cp[1].y - cp[0].y) * -cp[0].y + cp[0].x * (cp[0].x - cp[1].x);
+        if edge < 0.0 {
+            return None;
+        }
+        let edge = (cp[2].y - cp[3].y) * -cp[3].y + cp[3].x * (cp[3].x - cp[2].x);
+        if edge < 0.0 {
+            return None;
+        }
+
+        // At this depth the curve is close enough to the line between its end points
+        let (dx, dy) = (cp[3].x - cp[0].x, cp[3].y - cp[0].y);
+        let denominator = dx * dx + dy * dy;
+        if denominator == 0.0 {
+            return None;
+        }
+        let w = ((-cp[0].x * dx - cp[0].y * dy) / denominator).clamp(0.0, 1.0);
+        let u = lerp(w, u0, u1);
+
+        let (pc, _) = eval_bezier(cp, w);
+        let half_width = 0.5 * self.width(u);
+        if pc.x * pc.x + pc.y * pc.y > half_width * half_width {
+            return None;
+        }
+        if pc.z <= z_min || pc.z >= z_max {
+            return None;
+        }
+
+        Some((pc.z, u))
+    }
+}
+
+impl Hittable for Curve {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = ray.direction.magnitude();
+        let direction = ray.direction / length;
+        let helper = if direction.x.abs() > 0.9 {
+            vec3(0.0, 1.0, 0.0)
+        } else {
+            vec3(1.0, 0.0, 0.0)
+        };
+        let ex = direction.cross(helper).normalize();
+        let ey = direction.cross(ex);
+        let to_ray = |p: &Point3<f64>| {
+            let offset = p - ray.origin;
+            vec3(offset.dot(ex), offset.dot(ey), offset.dot(direction))
+        };
+        let cp = [
+            to_ray(&self.points[0]),
+            to_ray(&self.points[1]),
+            to_ray(&self.points[2]),
+            to_ray(&self.points[3]),
+        ];
+
+        // Subdivides until the pieces are within a small fraction of the width of a line
+        let mut l0: f64 = 0.0;
+        for i in 0..2 {
+            let d = cp[i] - 2.0 * cp[i + 1] + cp[i + 2];
+            l0 = l0.max(d.x.abs()).max(d.y.abs()).max(d.z.abs());
+        }
+        let epsilon = 0.05 * self.widths[0].max(self.widths[1]);
+        let depth = if l0 > 0.0 && epsilon > 0.0 {
+            ((f64::consts::SQRT_2 * 6.0 * l0 / (8.0 * epsilon))
+                .log2()
+                .floor() as i32
+                / 2)
+            .clamp(0, 10)
+        } else {
+            0
+        };
+
+        let (z, u) = self.intersect(&cp, 0.0, 1.0, depth, t_min * length, t_max * length)?;
+        let t = z / length;
+        let point = ray.point_at(t);
+
+        let world: [Vector3<f64>; 4] = [
+            self.points[0].to_vec(),
+            self.points[1].to_vec(),
+            self.points[2].to_vec(),
+            self.points[3].to_vec(),
+        ];
+        let (center, derivative) = eval_bezier(&world, u);
+        let tangent = derivative.normalize();
+
+        // `facing` points back along the ray, `across` runs over the width of the strip
+        let facing = -direction + tangent * direction.dot(tangent);
+        let facing = if facing.magnitude2() > 0.0 {
+            facing.normalize()
+        } else {
+            tangent.cross(ex).normalize()
+        };
+        let across = facing.cross(tangent);
+        let h = ((point - center).dot(across) / (0.5 * self.width(u))).clamp(-1.0, 1.0);
+
+        let normal = match self.kind {
+            CurveType::Flat => facing,
+            CurveType::Tube => facing * (1.0 - h * h).sqrt() + across * h,
+        };
+        let material = Arc::clone(&self.material);
+
+        let mut rec = HitRecord::new(ray, t, point, facing, material, u, 0.5 * (h + 1.0));
+        rec.set_shading_normal(normal);
+        rec.tangent = Some(tangent);
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let half_width = 0.5 * self.widths[0].max(self.widths[1]);
+        let mut min = self.points[0].to_vec();
+        let mut max = min;
+        for p in &self.points[1..] {
+            for a in 0..3 {
+                min[a] = min[a].min(p[a]);
+                max[a] = max[a].max(p[a]);
+            }
+        }
+
+        let pad = vec3(half_width, half_width, half_width);
+        Some(AABB::new(min - pad, max + pad))
+    }
+}
+
+// Many curves, such as the strands of hair or fur on a character, in a BVH of their own
+pub struct CurveSet {
+    bvh: BvhTree<Curve>,
+}
+
+impl CurveSet {
+    pub fn new(curves: Vec<Curve>) -> Self {
+        CurveSet {
+            bvh: BvhTree::new(curves, 0.0, 0.0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bvh.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bvh.is_empty()
+    }
+}
+
+impl Hittable for CurveSet {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hits(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.bvh.bounding_box(t0, t1)
+    }
+}
+
+fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    (1.0 - t) * a + t * b
+}
+
+// Splits the curve in half with de Casteljau's algorithm
+fn subdivide(cp: &[Vector3<f64>; 4]) -> [[Vector3<f64>; 4]; 2] {
+    let middle = (cp[0] + 3.0 * cp[1] + 3.0 * cp[2] + cp[3]) / 8.0;
+    [
+        [
+            cp[0],
+            (cp[0] + cp[1]) / 2.0,
+            (cp[0] + 2.0 * cp[1] + cp[2]) / 4.0,
+            middle,
+        ],
+        [
+            middle,
+            (cp[1] + 2.0 * cp[2] + cp[3]) / 4.0,
+            (cp[2] + cp[3]) / 2.0,
+            cp[3],
+        ],
+    ]
+}
+
+// Returns the point on the curve at u and the derivative there
+fn eval_bezier(cp: &[Vector3<f64>; 4], u: f64) -> (Vector3<f64>, Vector3<f64>) {
+    let a = [
+        cp[0].lerp(cp[1], u),
+        cp[1].lerp(cp[2], u),
+        cp[2].lerp(cp[3], u),
+    ];
+    let b = [a[0].lerp(a[1], u), a[1].lerp(a[2], u)];
+
+    // The derivative vanishes at the ends when control points coincide
+    let derivative = if (b[1] - b[0]).magnitude2() > 0.0 {
+        3.0 * (b[1] - b[0])
+    } else {
+        cp[3] - cp[0]
+    };
+
+    (b[0].lerp(b[1], u), derivative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::hair::Hair;
+    use crate::materials::Scatterable;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Hair(Hair::melanin(1.3, 0.0, 0.3, 0.3, 2.0)))
+    }
+
+    // A straight strip along x from -1 to 1, 0.2 wide
+    fn straight(kind: CurveType) -> Curve {
+        let points = [
+            Point3::new(-1.0, 0.0, 0.0),
+            Point3::new(-1.0 / 3.0, 0.0, 0.0),
+            Point3::new(1.0 / 3.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+        ];
+        Curve::new(points, [0.2, 0.2], kind, material())
+    }
+
+    #[test]
+    fn hits_the_ribbon() {
+        let ray = Ray::from(Point3::new(0.3, 0.05, -5.0), vec3(0.0, 0.0, 2.0), 0.0);
+        let hit = straight(CurveType::Flat)
+            .hits(&ray, 0.001, f64::MAX)
+            .unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-9);
+        assert!((hit.u - 0.65).abs() < 1e-9);
+        // Across the strip v runs from 0 to 1, and this side is half way out from the middle
+        assert!((hit.v - 0.25).abs() < 1e-9);
+        assert!((hit.normal - vec3(0.0, 0.0, -1.0)).magnitude() < 1e-9);
+        assert!((hit.tangent.unwrap() - vec3(1.0, 0.0, 0.0)).magnitude() < 1e-9);
+
+        // Tubes bend the shading normal towards the side of the strip that was hit
+        let hit = straight(CurveType::Tube)
+            .hits(&ray, 0.001, f64::MAX)
+            .unwrap();
+        let expected = vec3(0.0, 0.5, -0.75f64.sqrt());
+        assert!((hit.normal - expected).magnitude() < 1e-9);
+        assert_eq!(hit.geometric_normal, vec3(0.0, 0.0, -1.0));
+
+        let beside = Ray::from(Point3::new(0.3, 0.15, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!(straight(CurveType::Flat)
+            .hits(&beside, 0.001, f64::MAX)
+            .is_none());
+        let beyond = Ray::from(Point3::new(1.05, 0.0, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!(straight(CurveType::Flat)
+            .hits(&beyond, 0.001, f64::MAX)
+            .is_none());
+    }
+
+    #[test]
+    fn hits_bent_curves_along_their_middle() {
+        let points = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(2.0, 1.0, 0.0),
+            Point3::new(3.0, 0.0, 0.0),
+        ];
+        let curve = Curve::new(points, [0.1, 0.1], CurveType::Tube, material());
+
+        // The middle of the curve is at (1.5, 0.75, 0)
+        let ray = Ray::from(Point3::new(1.5, 0.75, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let hit = curve.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!((hit.u - 0.5).abs() < 1e-3);
+        let ray = Ray::from(Point3::new(1.5, 0.85, 5.0), vec3(0.0, 0.0, -1.0), 0.0);
+        assert!(curve.hits(&ray, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn scatters_off_hair_with_finite_weights() {
+        let ray = Ray::from(Point3::new(0.3, 0.05, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let hit = straight(CurveType::Tube)
+            .hits(&ray, 0.001, f64::MAX)
+            .unwrap();
+        for _ in 0..100 {
+            let (scattered, attenuation) = hit.material.scatter(&ray, &hit).unwrap();
+            assert!((scattered.direction.magnitude() - 1.0).abs() < 1e-9);
+            for a in 0..3 {
+                assert!(attenuation[a].is_finite() && attenuation[a] >= 0.0);
+            }
+        }
+    }
+}
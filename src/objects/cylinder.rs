@@ -0,0 +1,164 @@
+use super::disk::disk_uv;
+use super::disk::hit_disk;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+
+use std::sync::Arc;
+
+// A cylinder standing on the disk of `radius` around `base` and extending `height` along +y.
+// Capped cylinders are closed at both ends, otherwise only the side is hit.
+pub struct Cylinder {
+    pub base: Point3<f64>,
+    pub radius: f64,
+    pub height: f64,
+    pub capped: bool,
+    material: Arc<Material>,
+}
+
+impl Cylinder {
+    pub fn new(base: Point3<f64>, radius: f64, height: f64, material: Arc<Material>) -> Self {
+        Cylinder {
+            base,
+            radius,
+            height,
+            capped: false,
+            material,
+        }
+    }
+
+    pub fn capped(base: Point3<f64>, radius: f64, height: f64, material: Arc<Material>) -> Self {
+        Cylinder {
+            capped: true,
+            ..Cylinder::new(base, radius, height, material)
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let oc = ray.origin - self.base;
+        let d = ray.direction;
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (oc.x * d.x + oc.z * d.z);
+        let c = oc.x * oc.x + oc.z * oc.z - self.radius * self.radius;
+
+        let mut closest = t_max;
+        let mut hit = None;
+
+        let discriminant = b * b - 4.0 * a * c;
+        if a != 0.0 && discriminant >= 0.0 {
+            let root = discriminant.sqrt();
+            for &t in &[(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)] {
+                let y = oc.y + t * d.y;
+                if t > t_min && t < closest && y >= 0.0 && y <= self.height {
+                    let point = ray.point_at(t);
+                    let offset = point - self.base.to_vec();
+                    let normal = vec3(offset.x, 0.0, offset.z) / self.radius;
+                    let u = super::get_angle_uv(offset.x, offset.z);
+                    let v = y / self.height;
+                    let material = Arc::clone(&self.material);
+                    closest = t;
+                    hit = Some(HitRecord::new(ray, t, point, normal, material, u, v));
+                    break;
+                }
+            }
+        }
+
+        if self.capped {
+            let top = self.base + vec3(0.0, self.height, 0.0);
+            for &(center, normal) in &[(self.base, -1.0), (top, 1.0)] {
+                if let Some((t, point)) = hit_disk(center, 0.0, self.radius, ray, t_min, closest) {
+                    let (u, v) = disk_uv(point - center, 0.0, self.radius);
+                    let normal = vec3(0.0, normal, 0.0);
+                    let material = Arc::clone(&self.material);
+                    closest = t;
+                    hit = Some(HitRecord::new(
+                        ray,
+                        t,
+                        point.to_vec(),
+                        normal,
+                        material,
+                        u,
+                        v,
+                    ));
+                }
+            }
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(AABB::new(
+            self.base.to_vec() - vec3(self.radius, 0.0, self.radius),
+            self.base.to_vec() + vec3(self.radius, self.height, self.radius),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn hits_the_side() {
+        let cylinder = Cylinder::new(Point3::new(1.0, 0.0, 0.0), 0.5, 2.0, material());
+
+        let ray = Ray::from(Point3::new(-2.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = cylinder.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 2.5);
+        assert_eq!(hit.normal, vec3(-1.0, 0.0, 0.0));
+        assert_eq!(hit.v, 0.5);
+        assert!(hit.front_face);
+
+        // Open cylinders are seen from the inside through their ends
+        let ray = Ray::from(Point3::new(1.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = cylinder.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 0.5);
+        assert_eq!(hit.normal, vec3(-1.0, 0.0, 0.0));
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn misses_beyond_the_ends() {
+        let cylinder = Cylinder::new(Point3::new(1.0, 0.0, 0.0), 0.5, 2.0, material());
+
+        let over = Ray::from(Point3::new(-2.0, 2.5, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(cylinder.hits(&over, 0.001, f64::MAX).is_none());
+        let beside = Ray::from(Point3::new(-2.0, 1.0, 0.6), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(cylinder.hits(&beside, 0.001, f64::MAX).is_none());
+        let through = Ray::from(Point3::new(1.2, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert!(cylinder.hits(&through, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn hits_the_caps() {
+        let cylinder = Cylinder::capped(Point3::new(1.0, 0.0, 0.0), 0.5, 2.0, material());
+
+        let down = Ray::from(Point3::new(1.2, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = cylinder.hits(&down, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 3.0);
+        assert_eq!(hit.normal, vec3(0.0, 1.0, 0.0));
+        let up = Ray::from(Point3::new(1.2, -1.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+        let hit = cylinder.hits(&up, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.normal, vec3(0.0, -1.0, 0.0));
+        assert!(hit.front_face);
+
+        let bx = cylinder.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!(bx.min, vec3(0.5, 0.0, -0.5));
+        assert_eq!(bx.max, vec3(1.5, 2.0, 0.5));
+    }
+}
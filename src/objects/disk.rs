@@ -0,0 +1,200 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::sync::Arc;
+
+// Disks are infinitely thin, so their boxes are padded along y like the rectangles
+const THICKNESS: f64 = 0.0001;
+
+// A disk of `radius` around `center` in the plane y = center.y, facing +y
+pub struct Disk {
+    pub center: Point3<f64>,
+    pub radius: f64,
+    material: Arc<Material>,
+}
+
+impl Disk {
+    pub fn new(center: Point3<f64>, radius: f64, material: Arc<Material>) -> Self {
+        Disk {
+            center,
+            radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t, point) = hit_disk(self.center, 0.0, self.radius, ray, t_min, t_max)?;
+        let (u, v) = disk_uv(point - self.center, 0.0, self.radius);
+        let normal = vec3(0.0, 1.0, 0.0);
+        let material = Arc::clone(&self.material);
+        Some(HitRecord::new(
+            ray,
+            t,
+            point.to_vec(),
+            normal,
+            material,
+            u,
+            v,
+        ))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(disk_box(self.center, self.radius))
+    }
+}
+
+// A flat ring between `inner_radius` and `outer_radius` in the plane y = center.y, facing +y
+pub struct Annulus {
+    pub center: Point3<f64>,
+    pub inner_radius: f64,
+    pub outer_radius: f64,
+    material: Arc<Material>,
+}
+
+impl Annulus {
+    pub fn new(
+        center: Point3<f64>,
+        inner_radius: f64,
+        outer_radius: f64,
+        material: Arc<Material>,
+    ) -> Self {
+        Annulus {
+            center,
+            inner_radius,
+            outer_radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Annulus {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (t, point) = hit_disk(
+            self.center,
+            self.inner_radius,
+            self.outer_radius,
+            ray,
+            t_min,
+            t_max,
+        )?;
+        let (u, v) = disk_uv(point - self.center, self.inner_radius, self.outer_radius);
+        let normal = vec3(0.0, 1.0, 0.0);
+        let material = Arc::clone(&self.material);
+        Some(HitRecord::new(
+            ray,
+            t,
+            point.to_vec(),
+            normal,
+            material,
+            u,
+            v,
+        ))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(disk_box(self.center, self.outer_radius))
+    }
+}
+
+// Intersects the ray with the ring between `inner` and `outer` around `center` in the plane
+// y = center.y. Also used for the caps of cylinders and cones.
+pub(crate) fn hit_disk(
+    center: Point3<f64>,
+    inner: f64,
+    outer: f64,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, Point3<f64>)> {
+    let t = (center.y - ray.origin.y) / ray.direction.y;
+    if !(t > t_min && t < t_max) {
+        return None;
+    }
+
+    let point = ray.origin + t * ray.direction;
+    let distance2 = (point.x - center.x).powi(2) + (point.z - center.z).powi(2);
+    if distance2 > outer * outer || distance2 < inner * inner {
+        return None;
+    }
+
+    Some((t, Point3::new(point.x, center.y, point.z)))
+}
+
+// u goes around the disk and v runs from the outer edge inwards
+pub(crate) fn disk_uv(offset: Vector3<f64>, inner: f64, outer: f64) -> (f64, f64) {
+    let distance = (offset.x * offset.x + offset.z * offset.z).sqrt();
+    let u = super::get_angle_uv(offset.x, offset.z);
+    let v = (outer - distance) / (outer - inner);
+
+    (u, v)
+}
+
+fn disk_box(center: Point3<f64>, radius: f64) -> AABB {
+    let bx = AABB::new(
+        center.to_vec() - vec3(radius, 0.0, radius),
+        center.to_vec() + vec3(radius, 0.0, radius),
+    );
+    bx.pad(THICKNESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    #[test]
+    fn hits_within_the_radius() {
+        let disk = Disk::new(Point3::new(0.0, 1.0, 0.0), 2.0, material());
+
+        let ray = Ray::from(Point3::new(1.0, 3.0, 1.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = disk.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.p, vec3(1.0, 1.0, 1.0));
+        assert_eq!(hit.normal, vec3(0.0, 1.0, 0.0));
+        assert_eq!(hit.v, (2.0 - 2.0f64.sqrt()) / 2.0);
+
+        let outside = Ray::from(Point3::new(1.5, 3.0, 1.5), vec3(0.0, -1.0, 0.0), 0.0);
+        assert!(disk.hits(&outside, 0.001, f64::MAX).is_none());
+        let parallel = Ray::from(Point3::new(0.0, 1.5, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(disk.hits(&parallel, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn leaves_the_hole_of_rings() {
+        let ring = Annulus::new(Point3::new(0.0, 1.0, 0.0), 1.0, 2.0, material());
+
+        let hole = Ray::from(Point3::new(0.5, 3.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert!(ring.hits(&hole, 0.001, f64::MAX).is_none());
+        let ray = Ray::from(Point3::new(1.5, 3.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = ring.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.v, 0.5);
+
+        let up = Ray::from(Point3::new(0.0, -1.0, 1.5), vec3(0.0, 1.0, 0.0), 0.0);
+        let hit = ring.hits(&up, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.normal, vec3(0.0, -1.0, 0.0));
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn bounds_tightly() {
+        let ring = Annulus::new(Point3::new(0.0, 1.0, 0.0), 1.0, 2.0, material());
+        let bx = ring.bounding_box(0.0, 1.0).unwrap();
+        assert_eq!(bx.min, vec3(-2.0, 1.0 - THICKNESS / 2.0, -2.0));
+        assert_eq!(bx.max, vec3(2.0, 1.0 + THICKNESS / 2.0, 2.0));
+    }
+}
@@ -0,0 +1,313 @@
+use super::camera::Camera;
+use super::mesh::position_key;
+use super::mesh::TriangleMesh;
+use crate::textures::Texture;
+use crate::textures::Textured;
+
+use cgmath::prelude::*;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Each edge of the original mesh is split into at most 2^MAX_LEVEL pieces
+const MAX_LEVEL: u32 = 10;
+// Faces of the displaced mesh meeting at a sharper angle than this keep a hard edge
+const CREASE_ANGLE: f64 = 60.0;
+
+// How finely meshes are diced for the image they are rendered into
+pub struct Tessellation {
+    pub camera: Camera,
+    pub image_height: u16,
+    // Longest an edge may appear on screen, in pixels
+    pub edge_length: f64,
+    // Budget for the whole diced mesh. Edges are split less finely everywhere to stay within
+    // it, which keeps the mesh free of cracks.
+    pub max_triangles: usize,
+}
+
+impl Tessellation {
+    pub fn new(camera: Camera, image_height: u16, edge_length: f64) -> Self {
+        Tessellation {
+            camera,
+            image_height,
+            edge_length,
+            max_triangles: 1 << 22,
+        }
+    }
+
+    // Only depends on the edge itself, so both triangles along an edge split it the same way
+    // and the diced mesh has no cracks. Edges outside the view are measured by their distance
+    // to the camera as well, since they can still show up in reflections.
+    fn splits(&self, a: Point3<f64>, b: Point3<f64>, level: u32, max_level: u32) -> bool {
+        if level >= max_level {
+            return false;
+        }
+
+        let distance = (a.midpoint(b) - self.camera.origin).magnitude();
+        let pixel = self.camera.pixel_size(distance, self.image_height);
+        (b - a).magnitude() > self.edge_length * pixel
+    }
+}
+
+// Dices `mesh` until its edges are at most `tessellation.edge_length` pixels long, then moves
+// each vertex along its normal by `scale` times the brightness of `texture`. Vertices that share
+// a position are moved together, so seams and hard edges do not open up.
+pub fn displace(
+    mesh: &TriangleMesh,
+    texture: &Texture,
+    scale: f64,
+    tessellation: &Tessellation,
+) -> TriangleMesh {
+    let normals = match mesh.normals {
+        Some(ref normals) => normals.clone(),
+        None => face_weighted_normals(mesh),
+    };
+
+    // Every level can quadruple the triangles, so a dicing over budget is retried one level
+    // coarser. Dicing stops as soon as the budget runs out, which bounds every attempt.
+    let mut max_level = MAX_LEVEL + 1;
+    let dicer = loop {
+        max_level -= 1;
+        let mut dicer = Dicer {
+            tessellation,
+            max_level,
+            positions: mesh.positions.clone(),
+            normals: normals.clone(),
+            uvs: mesh.uvs.clone(),
+            colors: mesh.colors.clone(),
+            midpoints: HashMap::new(),
+            indices: Vec::new(),
+            face_materials: Vec::new(),
+        };
+        for (&face, &material) in mesh.indices.iter().zip(&mesh.face_materials) {
+            if dicer.indices.len() > tessellation.max_triangles {
+                break;
+            }
+            dicer.dice(face, [0, 0, 0], material);
+        }
+
+        if dicer.indices.len() <= tessellation.max_triangles || max_level == 0 {
+            break dicer;
+        }
+    };
+
+    let mut groups: HashMap<[u64; 3], (Vector3<f64>, f64, usize)> = HashMap::new();
+    for i in 0..dicer.positions.len() {
+        let p = dicer.positions[i];
+        let (u, v) = dicer.uvs.as_ref().map_or((0.0, 0.0), |uvs| uvs[i]);
+        let value = texture.value(u, v, p.to_vec());
+        let group = groups
+            .entry(position_key(&p))
+            .or_insert((Vector3::zero(), 0.0, 0));
+        group.0 += dicer.normals[i];
+        group.1 += (value.x + value.y + value.z) / 3.0;
+        group.2 += 1;
+    }
+
+    let positions = dicer
+        .positions
+        .iter()
+        .map(|p| {
+            let (normal, height, count) = groups[&position_key(p)];
+            if normal.magnitude2() > 0.0 {
+                p + normal.normalize() * scale * height / count as f64
+            } else {
+                *p
+            }
+        })
+        .collect();
+
+    let mut displaced = TriangleMesh::new(Arc::clone(&mesh.materials[0]));
+    displaced.materials = mesh.materials.clone();
+    displaced.positions = positions;
+    displaced.uvs = dicer.uvs;
+    displaced.colors = dicer.colors;
+    displaced.indices = dicer.indices;
+    displaced.face_materials = dicer.face_materials;
+    displaced.compute_normals(CREASE_ANGLE);
+    displaced
+}
+
+// Area weighted normals of the faces around each position, for meshes without normals
+fn face_weighted_normals(mesh: &TriangleMesh) -> Vec<Vector3<f64>> {
+    let mut sums: HashMap<[u64; 3], Vector3<f64>> = HashMap::new();
+    for face in &mesh.indices {
+        let [p0, p1, p2] = [
+            mesh.positions[face[0] as usize],
+            mesh.positions[face[1] as usize],
+            mesh.positions[face[2] as usize],
+        ];
+        let normal = (p1 - p0).cross(p2 - p0);
+        for p in &[p0, p1, p2] {
+            *sums.entry(position_key(p)).or_insert_with(Vector3::zero) += normal;
+        }
+    }
+
+    mesh.positions
+        .iter()
+        .map(|p| {
+            sums.get(&position_key(p))
+                .cloned()
+                .unwrap_or_else(Vector3::zero)
+        })
+        .collect()
+}
+
+struct Dicer<'a> {
+    tessellation: &'a Tessellation,
+    max_level: u32,
+    positions: Vec<Point3<f64>>,
+    normals: Vec<Vector3<f64>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    colors: Option<Vec<Vector3<f64>>>,
+    midpoints: HashMap<(u32, u32), u32>,
+    indices: Vec<[u32; 3]>,
+    face_materials: Vec<u32>,
+}
+
+impl<'a> Dicer<'a> {
+    // `levels` counts how often each edge, from face[k] to face[k + 1], has been halved
+    fn dice(&mut self, face: [u32; 3], levels: [u32; 3], material: u32) {
+        let split: Vec<usize> = (0..3)
+            .filter(|&k| {
+                let a = self.positions[face[k] as usize];
+                let b = self.positions[face[(k + 1) % 3] as usize];
+                self.tessellation.splits(a, b, levels[k], self.max_level)
+            })
+            .collect();
+        // Edges inside the face are never shared, so their level only has to keep growing
+        let inner = levels.iter().max().unwrap() + 1;
+
+        match split.len() {
+            0 => {
+                self.indices.push(face);
+                self.face_materials.push(material);
+            }
+            3 => {
+                let [a, b, c] = face;
+                let [l0, l1, l2] = levels;
+                let (ab, bc, ca) = (
+                    self.midpoint(a, b),
+                    self.midpoint(b, c),
+                    self.midpoint(c, a),
+                );
+                self.dice([a, ab, ca], [l0 + 1, inner, l2 + 1], material);
+                self.dice([ab, b, bc], [l0 + 1, l1 + 1, inner], material);
+                self.dice([ca, bc, c], [inner, l1 + 1, l2 + 1], material);
+                self.dice([ab, bc, ca], [inner, inner, inner], material);
+            }
+            _ => {
+                // Halves the longest edge that needs it, the others are split further down
+                let length = |k: usize| {
+                    let a = self.positions[face[k] as usize];
+                    (self.positions[face[(k + 1) % 3] as usize] - a).magnitude2()
+                };
+                let k = *split
+                    .iter()
+                    .max_by(|&&i, &&j| length(i).total_cmp(&length(j)))
+                    .unwrap();
+                let (a, b, c) = (face[k], face[(k + 1) % 3], face[(k + 2) % 3]);
+                let (la, lb, lc) = (levels[k], levels[(k + 1) % 3], levels[(k + 2) % 3]);
+                let m = self.midpoint(a, b);
+                self.dice([a, m, c], [la + 1, inner, lc], material);
+                self.dice([m, b, c], [la + 1, lb, inner], material);
+            }
+        }
+    }
+
+    fn midpoint(&mut self, a: u32, b: u32) -> u32 {
+        let key = if a < b { (a, b) } else { (b, a) };
+        if let Some(&m) = self.midpoints.get(&key) {
+            return m;
+        }
+
+        let (i, j) = (a as usize, b as usize);
+        let m = self.positions.len() as u32;
+        self.positions
+            .push(self.positions[i].midpoint(self.positions[j]));
+        let normal = self.normals[i] + self.normals[j];
+        self.normals.push(if normal.magnitude2() > 0.0 {
+            normal.normalize()
+        } else {
+            normal
+        });
+        if let Some(ref mut uvs) = self.uvs {
+            uvs.push((0.5 * (uvs[i].0 + uvs[j].0), 0.5 * (uvs[i].1 + uvs[j].1)));
+        }
+        if let Some(ref mut colors) = self.colors {
+            colors.push(0.5 * (colors[i] + colors[j]));
+        }
+        self.midpoints.insert(key, m);
+        m
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::materials::Material;
+    use crate::textures::constant_texture::ConstantTexture;
+
+    use cgmath::vec3;
+
+    // A unit square in the xz plane facing up, as two triangles without normals
+    fn quad() -> TriangleMesh {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        let mut mesh = TriangleMesh::new(material);
+        mesh.positions = vec![
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 1.0),
+            Point3::new(0.0, 0.0, 1.0),
+        ];
+        mesh.add_triangle([0, 3, 1], 0);
+        mesh.add_triangle([1, 3, 2], 0);
+        mesh
+    }
+
+    fn tessellation(edge_length: f64) -> Tessellation {
+        let camera = Camera::new(
+            Point3::new(0.5, 3.0, -3.0),
+            Point3::new(0.5, 0.0, 0.5),
+            vec3(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        Tessellation::new(camera, 100, edge_length)
+    }
+
+    #[test]
+    fn moves_vertices_along_the_normal() {
+        let texture = Texture::ConstantTexture(ConstantTexture::from(0.5, 0.5, 0.5));
+        let displaced = displace(&quad(), &texture, 0.2, &tessellation(4.0));
+        assert!(displaced.len() > 2);
+
+        for p in &displaced.positions {
+            assert!((p.y - 0.1).abs() < 1e-12);
+            assert!(p.x >= 0.0 && p.x <= 1.0 && p.z >= 0.0 && p.z <= 1.0);
+        }
+        for normal in displaced.normals.as_ref().unwrap() {
+            assert!((normal - vec3(0.0, 1.0, 0.0)).magnitude() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn stays_within_the_triangle_budget() {
+        let texture = Texture::ConstantTexture(ConstantTexture::from(0.5, 0.5, 0.5));
+        let fine = displace(&quad(), &texture, 0.2, &tessellation(0.5));
+        assert!(fine.len() > 200);
+
+        let mut tessellation = tessellation(0.5);
+        tessellation.max_triangles = 200;
+        let capped = displace(&quad(), &texture, 0.2, &tessellation);
+        assert!(capped.len() <= 200 && capped.len() > 2);
+
+        tessellation.max_triangles = 1;
+        assert_eq!(displace(&quad(), &texture, 0.2, &tessellation).len(), 2);
+    }
+}
@@ -0,0 +1,400 @@
+use super::triangle;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+use crate::textures::noise_texture::Perlin;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+use image::GrayImage;
+use image::ImageResult;
+
+use std::f64;
+use std::path::Path;
+use std::sync::Arc;
+
+// Keeps flat stretches of terrain from collapsing their boxes, which the slab test misses
+const THICKNESS: f64 = 0.0001;
+// Every level down the quadtree pops one node and pushes at most four, which covers the 64
+// levels of any grid a usize can index
+const STACK_SIZE: usize = 3 * 64 + 1;
+
+// Terrain over a regular grid of `width` by `depth` samples. The grid spans `size.x` along x
+// and `size.z` along z from `corner`, and a sample height h sits at y = corner.y + h * size.y.
+// Each cell is split into two triangles. Rays walk a quadtree of the lowest and highest point
+// under each block of cells front to back, so only cells near the ray are ever intersected.
+pub struct Heightfield {
+    pub corner: Point3<f64>,
+    pub size: Vector3<f64>,
+    width: usize,
+    depth: usize,
+    heights: Vec<f64>,
+    normals: Vec<Vector3<f64>>,
+    // levels[0] holds one (min, max) pair per cell, each further level halves both sides
+    levels: Vec<Level>,
+    material: Arc<Material>,
+}
+
+struct Level {
+    width: usize,
+    depth: usize,
+    bounds: Vec<(f64, f64)>,
+}
+
+impl Heightfield {
+    // `heights` holds `width` samples along x for each of the `depth` rows along z
+    pub fn new(
+        heights: Vec<f64>,
+        width: usize,
+        depth: usize,
+        corner: Point3<f64>,
+        size: Vector3<f64>,
+        material: Arc<Material>,
+    ) -> Self {
+        assert!(
+            width >= 2 && depth >= 2,
+            "Heightfield needs at least 2x2 samples"
+        );
+        assert_eq!(
+            heights.len(),
+            width * depth,
+            "Heightfield samples do not match the grid dimensions"
+        );
+
+        let mut field = Heightfield {
+            corner,
+            size,
+            width,
+            depth,
+            heights,
+            normals: Vec::new(),
+            levels: Vec::new(),
+            material,
+        };
+        field.normals = (0..width * depth)
+            .map(|i| field.vertex_normal(i % width, i / width))
+            .collect();
+        field.build_levels();
+
+        field
+    }
+
+    // Uses the brightness of a grayscale image, with the first row of pixels along min z
+    pub fn open<P: AsRef<Path>>(
+        path: P,
+        corner: Point3<f64>,
+        size: Vector3<f64>,
+        material: Arc<Material>,
+    ) -> ImageResult<Self> {
+        let image = image::open(path)?.to_luma();
+        Ok(Heightfield::from_image(&image, corner, size, material))
+    }
+
+    pub fn from_image(
+        image: &GrayImage,
+        corner: Point3<f64>,
+        size: Vector3<f64>,
+        material: Arc<Material>,
+    ) -> Self {
+        let (width, depth) = image.dimensions();
+        let heights = image
+            .pixels()
+            .map(|pixel| f64::from(pixel[0]) / 255.0)
+            .collect();
+
+        Heightfield::new(
+            heights,
+            width as usize,
+            depth as usize,
+            corner,
+            size,
+            material,
+        )
+    }
+
+    // Sums `octaves` of Perlin noise, each at twice the frequency and half the weight of the
+    // previous one, with the first spanning `scale` noise cells over the grid. Heights are
+    // remapped from the [-1, 1] range of the noise to [0, 1].
+    #[allow(clippy::too_many_arguments)]
+    pub fn from_noise(
+        noise: &Perlin,
+        width: usize,
+        depth: usize,
+        scale: f64,
+        octaves: usize,
+        corner: Point3<f64>,
+        size: Vector3<f64>,
+        material: Arc<Material>,
+    ) -> Self {
+        let mut heights = Vec::with_capacity(width * depth);
+        for j in 0..depth {
+            for i in 0..width {
+                let p = vec3(
+                    i as f64 / (width - 1) as f64,
+                    0.5,
+                    j as f64 / (depth - 1) as f64,
+                ) * scale;
+
+                let mut accum = 0.0;
+                let mut weight = 1.0;
+                let mut total = 0.0;
+                for octave in 0..octaves {
+                    accum += weight * noise.noise(p * 2f64.powi(octave as i32));
+                    total += weight;
+                    weight *= 0.5;
+                }
+                heights.push(0.5 * (1.0 + accum / total.max(1.0)));
+            }
+        }
+
+        Heightfield::new(heights, width, depth, corner, size, material)
+    }
+
+    fn cell_size(&self) -> (f64, f64) {
+        (
+            self.size.x / (self.width - 1) as f64,
+            self.size.z / (self.depth - 1) as f64,
+        )
+    }
+
+    fn height(&self, i: usize, j: usize) -> f64 {
+        self.heights[j * self.width + i]
+    }
+
+    fn vertex(&self, i: usize, j: usize) -> Point3<f64> {
+        let (dx, dz) = self.cell_size();
+        self.corner
+            + vec3(
+                i as f64 * dx,
+                self.height(i, j) * self.size.y,
+                j as f64 * dz,
+            )
+    }
+
+    // Central differences of the heights, one sided along the border
+    fn vertex_normal(&self, i: usize, j: usize) -> Vector3<f64> {
+        let (dx, dz) = self.cell_size();
+        let (i0, i1) = (i.saturating_sub(1), (i + 1).min(self.width - 1));
+        let (j0, j1) = (j.saturating_sub(1), (j + 1).min(self.depth - 1));
+        let slope_x =
+            (self.height(i1, j) - self.height(i0, j)) * self.size.y / ((i1 - i0) as f64 * dx);
+        let slope_z =
+            (self.height(i, j1) - self.height(i, j0)) * self.size.y / ((j1 - j0) as f64 * dz);
+
+        vec3(-slope_x, 1.0, -slope_z).normalize()
+    }
+
+    fn build_levels(&mut self) {
+        let (cells_x, cells_z) = (self.width - 1, self.depth - 1);
+        let mut bounds = Vec::with_capacity(cells_x * cells_z);
+        for j in 0..cells_z {
+            for i in 0..cells_x {
+                let corners = [
+                    self.height(i, j),
+                    self.height(i + 1, j),
+                    self.height(i, j + 1),
+                    self.height(i + 1, j + 1),
+                ];
+                let min = corners.iter().cloned().fold(f64::INFINITY, f64::min);
+                let max = corners.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+                bounds.push((min, max));
+            }
+        }
+        self.levels.push(Level {
+            width: cells_x,
+            depth: cells_z,
+            bounds,
+        });
+
+        loop {
+            let below = self.levels.last().unwrap();
+            if below.width == 1 && below.depth == 1 {
+                break;
+            }
+
+            // Halves both sides, rounding up
+            let (width, depth) = (below.width - below.width / 2, below.depth - below.depth / 2);
+            let mut bounds = vec![(f64::INFINITY, f64::NEG_INFINITY); width * depth];
+            for j in 0..below.depth {
+                for i in 0..below.width {
+                    let (min, max) = below.bounds[j * below.width + i];
+                    let bound = &mut bounds[(j / 2) * width + i / 2];
+                    bound.0 = bound.0.min(min);
+                    bound.1 = bound.1.max(max);
+                }
+            }
+            self.levels.push(Level {
+                width,
+                depth,
+                bounds,
+            });
+        }
+    }
+
+    fn node_box(&self, level: usize, i: usize, j: usize) -> AABB {
+        let (dx, dz) = self.cell_size();
+        let cells = 1 << level;
+        let (x0, x1) = (i * cells, ((i + 1) * cells).min(self.width - 1));
+        let (z0, z1) = (j * cells, ((j + 1) * cells).min(self.depth - 1));
+        let (min, max) = self.levels[level].bounds[j * self.levels[level].width + i];
+
+        let bx = AABB::new(
+            self.corner.to_vec() + vec3(x0 as f64 * dx, min * self.size.y, z0 as f64 * dz),
+            self.corner.to_vec() + vec3(x1 as f64 * dx, max * self.size.y, z1 as f64 * dz),
+        );
+        bx.pad(THICKNESS)
+    }
+
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let corners = [(i, j), (i, j + 1), (i + 1, j)];
+        let first = self.hit_triangle(ray, corners, t_min, t_max);
+        let t_max = first.as_ref().map_or(t_max, |hit| hit.t);
+        let corners = [(i + 1, j), (i, j + 1), (i + 1, j + 1)];
+        self.hit_triangle(ray, corners, t_min, t_max).or(first)
+    }
+
+    fn hit_triangle(
+        &self,
+        ray: &Ray,
+        corners: [(usize, usize); 3],
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<HitRecord> {
+        let [c0, c1, c2] = corners;
+        let (p0, p1, p2) = (
+            self.vertex(c0.0, c0.1),
+            self.vertex(c1.0, c1.1),
+            self.vertex(c2.0, c2.1),
+        );
+        let (t, b0, b1, b2) = triangle::intersect(p0, p1, p2, ray, t_min, t_max)?;
+
+        let normal = (b0 * self.normals[c0.1 * self.width + c0.0]
+            + b1 * self.normals[c1.1 * self.width + c1.0]
+            + b2 * self.normals[c2.1 * self.width + c2.0])
+            .normalize();
+        let mut geometric = (p1 - p0).cross(p2 - p0).normalize();
+        if geometric.dot(normal) < 0.0 {
+            geometric = -geometric;
+        }
+        let point = b0 * p0.to_vec() + b1 * p1.to_vec() + b2 * p2.to_vec();
+        // The first row of an image lies along min z, where image textures sample v = 1
+        let u = (point.x - self.corner.x) / self.size.x;
+        let v = 1.0 - (point.z - self.corner.z) / self.size.z;
+        let material = Arc::clone(&self.material);
+
+        let mut rec = HitRecord::new(ray, t, point, geometric, material, u, v);
+        rec.set_shading_normal(normal);
+        Some(rec)
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut closest = t_max;
+        let mut hit = None;
+
+        // Children are pushed back to front so the nearest is visited first
+        let flip_x = ray.direction.x < 0.0;
+        let flip_z = ray.direction.z < 0.0;
+        let mut stack = [(0, 0, 0); STACK_SIZE];
+        stack[0] = (self.levels.len() - 1, 0, 0);
+        let mut depth = 1;
+        while depth > 0 {
+            depth -= 1;
+            let (level, i, j) = stack[depth];
+            if !self.node_box(level, i, j).hit(ray, t_min, closest) {
+                continue;
+            }
+
+            if level == 0 {
+                if let Some(cell_hit) = self.hit_cell(ray, i, j, t_min, closest) {
+                    closest = cell_hit.t;
+                    hit = Some(cell_hit);
+                }
+                continue;
+            }
+
+            let below = &self.levels[level - 1];
+            for &(a, b) in &[(1, 1), (1, 0), (0, 1), (0, 0)] {
+                let ci = 2 * i + if flip_x { 1 - a } else { a };
+                let cj = 2 * j + if flip_z { 1 - b } else { b };
+                if ci < below.width && cj < below.depth {
+                    stack[depth] = (level - 1, ci, cj);
+                    depth += 1;
+                }
+            }
+        }
+
+        hit
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(self.node_box(self.levels.len() - 1, 0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    // 5 by 4 samples at height 0.25, with a plateau at 0.75 over the cell from (2, 1) to (3, 2).
+    // Cells are one unit wide and y spans 2 units, so the plateau sits at y = 1.5.
+    fn field() -> Heightfield {
+        let mut heights = vec![0.25; 20];
+        for &(i, j) in &[(2, 1), (3, 1), (2, 2), (3, 2)] {
+            heights[j * 5 + i] = 0.75;
+        }
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        Heightfield::new(
+            heights,
+            5,
+            4,
+            Point3::new(0.0, 0.0, 0.0),
+            vec3(4.0, 2.0, 3.0),
+            material,
+        )
+    }
+
+    #[test]
+    fn hits_cells_at_their_height() {
+        let field = field();
+        assert_eq!(field.levels.len(), 3);
+
+        let ray = Ray::from(Point3::new(2.5, 5.0, 1.5), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = field.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 3.5).abs() < 1e-9);
+        assert!((hit.geometric_normal - vec3(0.0, 1.0, 0.0)).magnitude() < 1e-9);
+        assert!(hit.front_face);
+        assert!((hit.u - 0.625).abs() < 1e-9 && (hit.v - 0.5).abs() < 1e-9);
+
+        let ray = Ray::from(Point3::new(0.5, 5.0, 2.5), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = field.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 4.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn misses_rays_grazing_over_the_top() {
+        let field = field();
+
+        for &(origin, direction) in &[
+            (Point3::new(-1.0, 1.51, 1.5), vec3(1.0, 0.0, 0.0)),
+            (Point3::new(5.0, 1.51, 1.6), vec3(-1.0, 0.0, 0.1)),
+            (Point3::new(-1.0, 1.51, -1.0), vec3(1.0, 0.0, 1.0)),
+            (Point3::new(2.5, 1.51, 4.0), vec3(0.0, 0.0, -1.0)),
+        ] {
+            let ray = Ray::from(origin, direction, 0.0);
+            assert!(field.hits(&ray, 0.001, f64::MAX).is_none());
+        }
+
+        // Just below the plateau the same ray runs into the slope up to it, which rises from
+        // y = 0.5 at x = 1 to y = 1.5 at x = 2
+        let ray = Ray::from(Point3::new(-1.0, 1.49, 1.5), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = field.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 2.99).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,159 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::henyey_greenstein::HenyeyGreenstein;
+use crate::materials::Material;
+use crate::ray::Ray;
+use crate::textures::constant_texture::ConstantTexture;
+use crate::textures::Texture;
+use crate::textures::Textured;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Vector3;
+use rand::prelude::*;
+
+use std::f64;
+use std::sync::Arc;
+
+// A volume inside a closed boundary whose density varies through space, driven by a 3D
+// texture such as `NoiseTexture` or `VoxelGrid`. The extinction at a point is
+// (sigma_a + sigma_s) * density, and collisions are found with delta tracking against the
+// largest possible extinction.
+// Refer: http://www.pbr-book.org/3ed-2018/Light_Transport_II_Volume_Rendering/Sampling_Volume_Scattering.html
+pub struct HeterogeneousMedium<T: Hittable> {
+    pub boundary: T,
+    density: Texture,
+    sigma_t: f64,
+    majorant: f64,
+    phase_function: Arc<Material>,
+}
+
+impl<T: Hittable> HeterogeneousMedium<T> {
+    pub fn new(
+        boundary: T,
+        density: Texture,
+        sigma_a: f64,
+        sigma_s: f64,
+        color: Vector3<f64>,
+        g: f64,
+    ) -> Self {
+        let sigma_t = sigma_a + sigma_s;
+        let albedo = if sigma_t > 0.0 {
+            color * (sigma_s / sigma_t)
+        } else {
+            color
+        };
+        let majorant = sigma_t * density.max_value();
+
+        HeterogeneousMedium {
+            boundary,
+            density,
+            sigma_t,
+            majorant,
+            phase_function: Arc::new(Material::HenyeyGreenstein(HenyeyGreenstein::new(
+                Texture::ConstantTexture(ConstantTexture::new(albedo)),
+                g,
+            ))),
+        }
+    }
+
+    fn extinction(&self, p: Vector3<f64>) -> f64 {
+        let density = self.density.value(0.0, 0.0, p);
+        self.sigma_t * (density.x + density.y + density.z) / 3.0
+    }
+
+    // Ray parameters where the ray enters and leaves the boundary, clipped to [t_min, t_max]
+    fn interval(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<(f64, f64)> {
+        let enter = self.boundary.hits(ray, f64::NEG_INFINITY, f64::INFINITY)?;
+        let exit = self.boundary.hits(ray, enter.t + 0.0001, f64::INFINITY)?;
+
+        let t_enter = enter.t.max(t_min).max(0.0);
+        let t_exit = exit.t.min(t_max);
+        if t_enter < t_exit {
+            Some((t_enter, t_exit))
+        } else {
+            None
+        }
+    }
+}
+
+impl<T: Hittable> Hittable for HeterogeneousMedium<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (mut t, t_exit) = self.interval(ray, t_min, t_max)?;
+        if self.majorant <= 0.0 {
+            return None;
+        }
+
+        // Delta tracking: sample tentative collisions against the majorant and accept them
+        // with probability proportional to the real extinction at that point
+        let step = self.majorant * ray.direction.magnitude();
+        let mut rng = thread_rng();
+        loop {
+            t -= (1.0 - rng.gen::<f64>()).ln() / step;
+            if t >= t_exit {
+                return None;
+            }
+
+            let point = ray.point_at(t);
+            if rng.gen::<f64>() * self.majorant < self.extinction(point) {
+                // The normal is meaningless inside a volume
+                let normal = vec3(1.0, 0.0, 0.0);
+                let material = Arc::clone(&self.phase_function);
+                return Some(HitRecord::new(ray, t, point, normal, material, 0.0, 0.0));
+            }
+        }
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.boundary.bounding_box(t0, t1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::objects::sphere::Sphere;
+    use crate::textures::voxel_grid::VoxelGrid;
+
+    use cgmath::Point3;
+
+    // A sphere of smoke filling the box from (-1, -1, -1) to (1, 1, 1), where the density is
+    // given by two voxels along x
+    fn smoke(voxels: [f32; 2]) -> HeterogeneousMedium<Sphere> {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        let boundary = Sphere::from(Point3::new(0.0, 0.0, 0.0), 1.0, material);
+        let grid = VoxelGrid::new(
+            [2, 1, 1],
+            voxels.to_vec(),
+            vec3(-1.0, -1.0, -1.0),
+            vec3(1.0, 1.0, 1.0),
+        );
+        let color = vec3(0.8, 0.8, 0.8);
+        HeterogeneousMedium::new(boundary, Texture::VoxelGrid(grid), 1.0, 100.0, color, 0.0)
+    }
+
+    #[test]
+    fn never_scatters_without_density() {
+        let smoke = smoke([0.0, 0.0]);
+        let ray = Ray::from(Point3::new(0.0, 0.0, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!((0..1000).all(|_| smoke.hits(&ray, 0.001, f64::MAX).is_none()));
+    }
+
+    #[test]
+    fn scatters_where_the_grid_is_dense() {
+        let smoke = smoke([0.0, 1.0]);
+
+        // Up to the center of the first voxel the density is clamped to its value of zero
+        let empty = Ray::from(Point3::new(-0.6, 0.0, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!((0..200).all(|_| smoke.hits(&empty, 0.001, f64::MAX).is_none()));
+
+        let dense = Ray::from(Point3::new(0.5, 0.0, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        for _ in 0..1000 {
+            let hit = smoke.hits(&dense, 0.001, f64::MAX).unwrap();
+            assert!(hit.t >= 3.0 - 0.75f64.sqrt() && hit.t < 3.0);
+            assert!(matches!(*hit.material, Material::HenyeyGreenstein(_)));
+        }
+    }
+}
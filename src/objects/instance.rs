@@ -0,0 +1,94 @@
+use super::transform::Transform;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::Matrix4;
+
+use std::sync::Arc;
+
+// A transformed reference to a shared object, typically one with its own `BvhTree`. Many
+// instances can be placed in the scene BVH while the geometry is only stored once.
+pub struct Instance {
+    transform: Transform<Arc<dyn Hittable>>,
+    material: Option<Arc<Material>>,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, matrix: Matrix4<f64>) -> Self {
+        Instance {
+            transform: Transform::new(object, matrix),
+            material: None,
+        }
+    }
+
+    pub fn with_material(
+        object: Arc<dyn Hittable>,
+        matrix: Matrix4<f64>,
+        material: Arc<Material>,
+    ) -> Self {
+        Instance {
+            transform: Transform::new(object, matrix),
+            material: Some(material),
+        }
+    }
+
+    pub fn object(&self) -> &Arc<dyn Hittable> {
+        &self.transform.hittable
+    }
+}
+
+impl Hittable for Instance {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut hit = self.transform.hits(ray, t_min, t_max)?;
+        if let Some(ref material) = self.material {
+            hit.material = Arc::clone(material);
+        }
+
+        Some(hit)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.transform.bounding_box(t0, t1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::materials::metal::Metal;
+    use crate::objects::sphere::Sphere;
+
+    use cgmath::vec3;
+    use cgmath::Point3;
+
+    fn sphere() -> Arc<dyn Hittable> {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        Arc::new(Sphere::from(Point3::new(0.0, 0.0, 0.0), 1.0, material))
+    }
+
+    #[test]
+    fn overrides_the_material() {
+        let sphere = sphere();
+        let matrix = Matrix4::from_translation(vec3(0.0, 0.0, 5.0));
+        let plain = Instance::new(Arc::clone(&sphere), matrix);
+        let metal = Arc::new(Material::Metal(Metal::from(0.9, 0.9, 0.9, 0.0)));
+        let shiny = Instance::with_material(Arc::clone(&sphere), matrix, metal);
+
+        let ray = Ray::from(Point3::new(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let hit = plain.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert!(matches!(*hit.material, Material::Lambertian(_)));
+        let hit = shiny.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 4.0);
+        assert!(matches!(*hit.material, Material::Metal(_)));
+
+        // The shared object keeps its own material
+        let ray = Ray::from(Point3::new(0.0, 0.0, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let hit = sphere.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!(matches!(*hit.material, Material::Lambertian(_)));
+    }
+}
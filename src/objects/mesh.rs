@@ -0,0 +1,482 @@
+use super::triangle;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::bvh::BvhTree;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::collections::HashMap;
+use std::iter::once;
+use std::sync::Arc;
+
+pub struct TriangleMesh {
+    pub positions: Vec<Point3<f64>>,
+    pub normals: Option<Vec<Vector3<f64>>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    pub colors: Option<Vec<Vector3<f64>>>,
+    pub indices: Vec<[u32; 3]>,
+    pub face_materials: Vec<u32>,
+    pub materials: Vec<Arc<Material>>,
+}
+
+impl TriangleMesh {
+    pub fn new(material: Arc<Material>) -> Self {
+        TriangleMesh {
+            positions: Vec::new(),
+            normals: None,
+            uvs: None,
+            colors: None,
+            indices: Vec::new(),
+            face_materials: Vec::new(),
+            materials: vec![material],
+        }
+    }
+
+    pub fn add_triangle(&mut self, indices: [u32; 3], material: u32) {
+        self.indices.push(indices);
+        self.face_materials.push(material);
+    }
+
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    // Replaces the vertex normals with area weighted averages of the adjacent face normals.
+    // Faces meeting at more than `crease_angle` degrees keep a hard edge, so vertices on a
+    // crease are split.
+    pub fn compute_normals(&mut self, crease_angle: f64) {
+        let cos_crease = crease_angle.to_radians().cos();
+
+        let face_normals: Vec<Vector3<f64>> = (0..self.len())
+            .map(|face| {
+                let [p0, p1, p2] = self.vertices(face);
+                (p1 - p0).cross(p2 - p0)
+            })
+            .collect();
+        let unit_normals: Vec<Vector3<f64>> = face_normals
+            .iter()
+            .map(|n| {
+                let magnitude = n.magnitude();
+                if magnitude > 0.0 {
+                    n / magnitude
+                } else {
+                    *n
+                }
+            })
+            .collect();
+
+        // Vertices that only differ in their UVs or colors still share a position
+        let mut groups: HashMap<[u64; 3], usize> = HashMap::new();
+        let vertex_groups: Vec<usize> = self
+            .positions
+            .iter()
+            .map(|p| {
+                let next = groups.len();
+                *groups.entry(position_key(p)).or_insert(next)
+            })
+            .collect();
+
+        let mut offsets = vec![0usize; groups.len() + 1];
+        for face in &self.indices {
+            for &i in face {
+                offsets[vertex_groups[i as usize] + 1] += 1;
+            }
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        let mut adjacent = vec![0usize; offsets[groups.len()]];
+        let mut filled = offsets.clone();
+        for (face, indices) in self.indices.iter().enumerate() {
+            for &i in indices {
+                let group = vertex_groups[i as usize];
+                adjacent[filled[group]] = face;
+                filled[group] += 1;
+            }
+        }
+
+        let mut vertices: HashMap<(u32, [u64; 3]), u32> = HashMap::new();
+        let mut positions = Vec::with_capacity(self.positions.len());
+        let mut normals = Vec::with_capacity(self.positions.len());
+        let mut uvs = self.uvs.as_ref().map(|_| Vec::new());
+        let mut colors = self.colors.as_ref().map(|_| Vec::new());
+
+        for face in 0..self.len() {
+            for corner in 0..3 {
+                let i = self.indices[face][corner];
+                let group = vertex_groups[i as usize];
+                let mut normal = Vector3::zero();
+                for &other in &adjacent[offsets[group]..offsets[group + 1]] {
+                    if unit_normals[face].dot(unit_normals[other]) >= cos_crease {
+                        normal += face_normals[other];
+                    }
+                }
+                let normal = if normal.magnitude2() > 0.0 {
+                    normal.normalize()
+                } else {
+                    unit_normals[face]
+                };
+
+                let next = positions.len() as u32;
+                let index = *vertices
+                    .entry((i, position_key(&Point3::from_vec(normal))))
+                    .or_insert(next);
+                if index == next {
+                    positions.push(self.positions[i as usize]);
+                    normals.push(normal);
+                    if let (Some(uvs), Some(old)) = (uvs.as_mut(), self.uvs.as_ref()) {
+                        uvs.push(old[i as usize]);
+                    }
+                    if let (Some(colors), Some(old)) = (colors.as_mut(), self.colors.as_ref()) {
+                        colors.push(old[i as usize]);
+                    }
+                }
+                self.indices[face][corner] = index;
+            }
+        }
+
+        self.positions = positions;
+        self.normals = Some(normals);
+        self.uvs = uvs;
+        self.colors = colors;
+    }
+
+    fn vertices(&self, face: usize) -> [Point3<f64>; 3] {
+        let [i0, i1, i2] = self.indices[face];
+        [
+            self.positions[i0 as usize],
+            self.positions[i1 as usize],
+            self.positions[i2 as usize],
+        ]
+    }
+
+    // Fills in a hit at barycentric coordinates `b` on a face with the given vertices, shaded
+    // with `normal` if the mesh has vertex normals
+    fn hit_record(
+        &self,
+        ray: &Ray,
+        face: usize,
+        vertices: [Point3<f64>; 3],
+        t: f64,
+        b: [f64; 3],
+        normal: Option<Vector3<f64>>,
+    ) -> HitRecord {
+        let [i0, i1, i2] = self.indices[face];
+        let [p0, p1, p2] = vertices;
+        let [b0, b1, b2] = b;
+        let point = b0 * p0.to_vec() + b1 * p1.to_vec() + b2 * p2.to_vec();
+        let mut geometric = (p1 - p0).cross(p2 - p0).normalize();
+        // Vertex normals tell which side is out, whichever way the face winds
+        if matches!(normal, Some(normal) if geometric.dot(normal) < 0.0) {
+            geometric = -geometric;
+        }
+        let (u, v) = match self.uvs {
+            Some(ref uvs) => {
+                let (uv0, uv1, uv2) = (uvs[i0 as usize], uvs[i1 as usize], uvs[i2 as usize]);
+                (
+                    b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0,
+                    b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1,
+                )
+            }
+            None => (b1, b2),
+        };
+        let material = Arc::clone(&self.materials[self.face_materials[face] as usize]);
+
+        let mut rec = HitRecord::new(ray, t, point, geometric, material, u, v);
+        if let Some(normal) = normal {
+            rec.set_shading_normal(normal);
+        }
+        if let Some(ref colors) = self.colors {
+            rec.color = Some(
+                b0 * colors[i0 as usize] + b1 * colors[i1 as usize] + b2 * colors[i2 as usize],
+            );
+        }
+
+        rec
+    }
+}
+
+pub(crate) fn position_key(p: &Point3<f64>) -> [u64; 3] {
+    // Adding 0.0 folds -0.0 into 0.0 so both hash the same
+    [
+        (p.x + 0.0).to_bits(),
+        (p.y + 0.0).to_bits(),
+        (p.z + 0.0).to_bits(),
+    ]
+}
+
+pub struct Mesh {
+    bvh: BvhTree<MeshTriangle>,
+}
+
+impl Mesh {
+    pub fn new(mesh: TriangleMesh) -> Mesh {
+        let mesh = Arc::new(mesh);
+        let triangles = (0..mesh.len())
+            .map(|face| MeshTriangle {
+                mesh: Arc::clone(&mesh),
+                face,
+            })
+            .collect();
+
+        Mesh {
+            bvh: BvhTree::new(triangles, 0.0, 0.0),
+        }
+    }
+}
+
+impl Hittable for Mesh {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hits(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.bvh.bounding_box(t0, t1)
+    }
+}
+
+struct MeshTriangle {
+    mesh: Arc<TriangleMesh>,
+    face: usize,
+}
+
+impl Hittable for MeshTriangle {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mesh = &self.mesh;
+        let [p0, p1, p2] = mesh.vertices(self.face);
+        let (t, b0, b1, b2) = triangle::intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let [i0, i1, i2] = mesh.indices[self.face];
+
+        let normal = mesh.normals.as_ref().map(|normals| {
+            (b0 * normals[i0 as usize] + b1 * normals[i1 as usize] + b2 * normals[i2 as usize])
+                .normalize()
+        });
+
+        Some(mesh.hit_record(ray, self.face, [p0, p1, p2], t, [b0, b1, b2], normal))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let [p0, p1, p2] = self.mesh.vertices(self.face);
+        Some(triangle::triangle_box(p0, p1, p2))
+    }
+}
+
+// A mesh whose vertices move between frames at increasing times, such as a cloth or fluid
+// cache. Each ray sees the vertices interpolated to its time, so the mesh blurs as it deforms.
+pub struct DeformingMesh {
+    bvh: BvhTree<DeformingTriangle>,
+}
+
+struct Frames {
+    times: Vec<f64>,
+    positions: Vec<Vec<Point3<f64>>>,
+    normals: Option<Vec<Vec<Vector3<f64>>>>,
+    // The first frame, which also holds the UVs, colors and materials of every frame
+    mesh: TriangleMesh,
+}
+
+impl DeformingMesh {
+    // Every frame needs the same vertices and triangles, only their positions and normals
+    // change. The mesh holds still before the first frame and after the last one. Bounds
+    // cover the motion between `time0` and `time1`.
+    pub fn new(mut frames: Vec<(f64, TriangleMesh)>, time0: f64, time1: f64) -> Self {
+        assert!(!frames.is_empty(), "DeformingMesh needs at least one frame");
+        frames.sort_by(|a, b| a.0.total_cmp(&b.0));
+        for (_, frame) in &frames[1..] {
+            assert!(
+                frame.positions.len() == frames[0].1.positions.len()
+                    && frame.indices == frames[0].1.indices,
+                "DeformingMesh frames differ in their vertices or triangles"
+            );
+        }
+
+        let times = frames.iter().map(|(time, _)| *time).collect();
+        let positions = frames
+            .iter()
+            .map(|(_, frame)| frame.positions.clone())
+            .collect();
+        let normals = if frames.iter().all(|(_, frame)| frame.normals.is_some()) {
+            Some(
+                frames
+                    .iter_mut()
+                    .map(|(_, frame)| frame.normals.take().unwrap())
+                    .collect(),
+            )
+        } else {
+            None
+        };
+        let mesh = frames.swap_remove(0).1;
+
+        let frames = Arc::new(Frames {
+            times,
+            positions,
+            normals,
+            mesh,
+        });
+        let triangles = (0..frames.mesh.len())
+            .map(|face| DeformingTriangle {
+                frames: Arc::clone(&frames),
+                face,
+            })
+            .collect();
+
+        DeformingMesh {
+            bvh: BvhTree::new(triangles, time0, time1),
+        }
+    }
+}
+
+impl Hittable for DeformingMesh {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        self.bvh.hits(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.bvh.bounding_box(t0, t1)
+    }
+}
+
+impl Frames {
+    // The frames around `time` and how far along from the first to the second it is
+    fn locate(&self, time: f64) -> (usize, usize, f64) {
+        let next = self.times.partition_point(|&t| t <= time);
+        if next == 0 {
+            return (0, 0, 0.0);
+        }
+        if next == self.times.len() {
+            return (next - 1, next - 1, 0.0);
+        }
+
+        let (t0, t1) = (self.times[next - 1], self.times[next]);
+        (next - 1, next, (time - t0) / (t1 - t0))
+    }
+
+    fn vertices(&self, face: usize, time: f64) -> [Point3<f64>; 3] {
+        let (a, b, s) = self.locate(time);
+        let vertex = |i: u32| {
+            let (p, q) = (self.positions[a][i as usize], self.positions[b][i as usize]);
+            p + (q - p) * s
+        };
+        let [i0, i1, i2] = self.mesh.indices[face];
+
+        [vertex(i0), vertex(i1), vertex(i2)]
+    }
+}
+
+struct DeformingTriangle {
+    frames: Arc<Frames>,
+    face: usize,
+}
+
+impl Hittable for DeformingTriangle {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let frames = &self.frames;
+        let [p0, p1, p2] = frames.vertices(self.face, ray.time);
+        let (t, b0, b1, b2) = triangle::intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let [i0, i1, i2] = frames.mesh.indices[self.face];
+
+        let normal = frames.normals.as_ref().map(|normals| {
+            let (a, b, s) = frames.locate(ray.time);
+            let normal = |i: u32| normals[a][i as usize].lerp(normals[b][i as usize], s);
+            (b0 * normal(i0) + b1 * normal(i1) + b2 * normal(i2)).normalize()
+        });
+
+        Some(
+            frames
+                .mesh
+                .hit_record(ray, self.face, [p0, p1, p2], t, [b0, b1, b2], normal),
+        )
+    }
+
+    // Vertices move in straight lines between frames, so the triangles at both ends of the
+    // interval and at every frame in between cover its sweep
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        let frames = &self.frames;
+        let inside = frames
+            .times
+            .iter()
+            .cloned()
+            .filter(|&time| time > t0 && time < t1);
+
+        once(t0)
+            .chain(inside)
+            .chain(once(t1))
+            .map(|time| {
+                let [p0, p1, p2] = frames.vertices(self.face, time);
+                triangle::triangle_box(p0, p1, p2)
+            })
+            .fold(None, |bx: Option<AABB>, triangle| {
+                Some(bx.map_or(triangle, |bx| bx.surrounding_box(&triangle)))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    use cgmath::vec3;
+
+    // One triangle parallel to the xy plane, at z = 0, 2 and 1 at times 0, 1 and 2
+    fn deforming(time0: f64, time1: f64) -> DeformingMesh {
+        let frames = [(1.0, 2.0), (0.0, 0.0), (2.0, 1.0)]
+            .iter()
+            .map(|&(time, z)| {
+                let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+                let mut mesh = TriangleMesh::new(material);
+                mesh.positions = vec![
+                    Point3::new(0.0, 0.0, z),
+                    Point3::new(1.0, 0.0, z),
+                    Point3::new(0.0, 1.0, z),
+                ];
+                mesh.add_triangle([0, 1, 2], 0);
+                (time, mesh)
+            })
+            .collect();
+
+        DeformingMesh::new(frames, time0, time1)
+    }
+
+    #[test]
+    fn hits_the_triangle_at_the_ray_time() {
+        let mesh = deforming(0.0, 2.0);
+
+        for &(time, z) in &[
+            (-1.0, 0.0),
+            (0.5, 1.0),
+            (1.0, 2.0),
+            (1.25, 1.75),
+            (3.0, 1.0),
+        ] {
+            let ray = Ray::from(Point3::new(0.2, 0.2, -5.0), vec3(0.0, 0.0, 1.0), time);
+            let hit = mesh.hits(&ray, 0.001, f64::MAX).unwrap();
+            assert!((hit.t - (5.0 + z)).abs() < 1e-9, "at time {}", time);
+        }
+
+        let ray = Ray::from(Point3::new(0.8, 0.8, -5.0), vec3(0.0, 0.0, 1.0), 0.5);
+        assert!(mesh.hits(&ray, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn bounds_the_frames_in_the_interval() {
+        // z is 0.5 at the start, 2 at the frame in between and 1.25 at the end. Flat triangles
+        // get slightly padded boxes.
+        let bx = deforming(0.25, 1.75).bounding_box(0.25, 1.75).unwrap();
+        assert!(bx.min.z <= 0.5 && bx.min.z > 0.499);
+        assert!(bx.max.z >= 2.0 && bx.max.z < 2.001);
+        assert!(bx.min.x <= 0.0 && bx.max.x >= 1.0 && bx.min.y <= 0.0 && bx.max.y >= 1.0);
+
+        let bx = deforming(1.5, 1.5).bounding_box(1.5, 1.5).unwrap();
+        assert!(bx.min.z <= 1.5 && bx.max.z >= 1.5);
+    }
+}
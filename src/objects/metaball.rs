@@ -0,0 +1,239 @@
+use super::get_sphere_uv;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::polynomial::solve_quartic;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::sync::Arc;
+
+// A source of the metaball field. It adds weight * (1 - d²/radius²)² at a distance d from the
+// center and nothing past `radius`. A negative weight carves into the other sources.
+#[derive(Debug, Clone, Copy)]
+pub struct Metaball {
+    pub center: Point3<f64>,
+    pub radius: f64,
+    pub weight: f64,
+}
+
+impl Metaball {
+    pub fn new(center: Point3<f64>, radius: f64, weight: f64) -> Self {
+        Metaball {
+            center,
+            radius,
+            weight,
+        }
+    }
+
+    fn field(&self, p: Point3<f64>) -> f64 {
+        let s = 1.0 - (p - self.center).magnitude2() / (self.radius * self.radius);
+        if s > 0.0 {
+            self.weight * s * s
+        } else {
+            0.0
+        }
+    }
+
+    fn gradient(&self, p: Point3<f64>) -> Vector3<f64> {
+        let r2 = self.radius * self.radius;
+        let offset = p - self.center;
+        let s = 1.0 - offset.magnitude2() / r2;
+        if s > 0.0 {
+            offset * (-4.0 * self.weight * s / r2)
+        } else {
+            Vector3::zero()
+        }
+    }
+
+    // Distances along the unit direction `d` where the ray is within the radius
+    fn interval(&self, origin: Point3<f64>, d: Vector3<f64>) -> Option<(f64, f64)> {
+        let oc = origin - self.center;
+        let b = oc.dot(d);
+        let c = oc.magnitude2() - self.radius * self.radius;
+        let discriminant = b * b - c;
+        if discriminant <= 0.0 {
+            return None;
+        }
+
+        let root = discriminant.sqrt();
+        Some((-b - root, -b + root))
+    }
+}
+
+// The surface where the summed field of the metaballs reaches `threshold`, which blends nearby
+// sources into one smooth blob
+pub struct Metaballs {
+    pub balls: Vec<Metaball>,
+    pub threshold: f64,
+    material: Arc<Material>,
+}
+
+impl Metaballs {
+    pub fn new(balls: Vec<Metaball>, threshold: f64, material: Arc<Material>) -> Self {
+        Metaballs {
+            balls,
+            threshold,
+            material,
+        }
+    }
+
+    pub fn field(&self, p: Point3<f64>) -> f64 {
+        self.balls.iter().map(|ball| ball.field(p)).sum()
+    }
+
+    fn gradient(&self, p: Point3<f64>) -> Vector3<f64> {
+        self.balls
+            .iter()
+            .fold(Vector3::zero(), |sum, ball| sum + ball.gradient(p))
+    }
+}
+
+impl Hittable for Metaballs {
+    // The ray is cut where it enters and leaves each ball. Between two cuts the same balls
+    // contribute, and the field along the ray is a quartic which is solved exactly.
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let length = ray.direction.magnitude();
+        let d = ray.direction / length;
+        let (s_min, s_max) = (t_min * length, t_max * length);
+
+        let mut cuts: Vec<(f64, usize)> = Vec::new();
+        for (i, ball) in self.balls.iter().enumerate() {
+            if let Some((enter, exit)) = ball.interval(ray.origin, d) {
+                if exit > s_min && enter < s_max {
+                    cuts.push((enter.max(s_min), i));
+                    cuts.push((exit.min(s_max), i));
+                }
+            }
+        }
+        cuts.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let mut active: Vec<usize> = Vec::new();
+        for window in cuts.windows(2) {
+            let (start, ball) = window[0];
+            match active.iter().position(|&i| i == ball) {
+                Some(index) => {
+                    active.swap_remove(index);
+                }
+                None => active.push(ball),
+            }
+
+            let end = window[1].0;
+            if active.is_empty() || end <= start {
+                continue;
+            }
+
+            // Solved for the distance x from the start of the piece, which keeps the
+            // coefficients small for distant origins
+            let origin = ray.origin + start * d;
+            let mut coefficients = [0.0, 0.0, 0.0, 0.0, -self.threshold];
+            for &i in &active {
+                let ball = &self.balls[i];
+                let r2 = ball.radius * ball.radius;
+                let oc = origin - ball.center;
+                // 1 - d²/radius² along the ray is a2·x² + a1·x + a0
+                let a2 = -1.0 / r2;
+                let a1 = -2.0 * oc.dot(d) / r2;
+                let a0 = 1.0 - oc.magnitude2() / r2;
+
+                let w = ball.weight;
+                coefficients[0] += w * a2 * a2;
+                coefficients[1] += w * 2.0 * a2 * a1;
+                coefficients[2] += w * (a1 * a1 + 2.0 * a2 * a0);
+                coefficients[3] += w * 2.0 * a1 * a0;
+                coefficients[4] += w * a0 * a0;
+            }
+
+            let [a, b, c, e, f] = coefficients;
+            // Rounding can leave a root of the piece just outside (t_min, t_max), so the later
+            // roots of the piece are tried as well
+            let t = solve_quartic(a, b, c, e, f)
+                .iter()
+                .filter(|&&x| x > 0.0 && x < end - start)
+                .map(|&x| (start + x) / length)
+                .find(|&t| t > t_min && t < t_max);
+            if let Some(t) = t {
+                let point = ray.point_at(t);
+                let p = Point3::from_vec(point);
+                // The field falls off away from the surface
+                let gradient = self.gradient(p);
+                let normal = if gradient.magnitude2() > 0.0 {
+                    -gradient.normalize()
+                } else {
+                    -d
+                };
+                let (u, v) = get_sphere_uv(normal);
+                let material = Arc::clone(&self.material);
+                return Some(HitRecord::new(ray, t, point, normal, material, u, v));
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        // Only balls adding to the field can raise it to a positive threshold
+        self.balls
+            .iter()
+            .filter(|ball| ball.weight > 0.0)
+            .map(|ball| {
+                let r = vec3(ball.radius, ball.radius, ball.radius);
+                AABB::new(ball.center.to_vec() - r, ball.center.to_vec() + r)
+            })
+            .fold(None, |bx: Option<AABB>, ball| {
+                Some(bx.map_or(ball, |bx| bx.surrounding_box(&ball)))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    // Reaches the threshold where (1 - d²/4)² = 1/4, so at a distance of √2 from the center
+    fn blob() -> Metaballs {
+        let ball = Metaball::new(Point3::new(1.0, 2.0, 3.0), 2.0, 1.0);
+        Metaballs::new(vec![ball], 0.25, material())
+    }
+
+    #[test]
+    fn hits_a_single_ball_at_its_iso_radius() {
+        let ray = Ray::from(Point3::new(-4.0, 2.0, 3.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = blob().hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - (5.0 - 2.0f64.sqrt())).abs() < 1e-9);
+        assert!((hit.normal - vec3(-1.0, 0.0, 0.0)).magnitude() < 1e-9);
+
+        let ray = Ray::from(Point3::new(1.0, 2.0, -2.0), vec3(0.0, 0.0, 2.0), 0.0);
+        let hit = blob().hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - (5.0 - 2.0f64.sqrt()) / 2.0).abs() < 1e-9);
+
+        // From the center the ray leaves through the back of the surface
+        let ray = Ray::from(Point3::new(1.0, 2.0, 3.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = blob().hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 2.0f64.sqrt()).abs() < 1e-9);
+        assert!(!hit.front_face);
+
+        let beside = Ray::from(Point3::new(-4.0, 3.5, 3.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(blob().hits(&beside, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn points_normals_away_from_the_center() {
+        let ray = Ray::from(Point3::new(1.0, 3.0, -2.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let hit = blob().hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 4.0).abs() < 1e-9);
+        let radial = (hit.p - vec3(1.0, 2.0, 3.0)).normalize();
+        assert!((hit.normal - radial).magnitude() < 1e-9);
+        assert!((hit.normal - vec3(0.0, 1.0, -1.0).normalize()).magnitude() < 1e-9);
+    }
+}
@@ -1,6 +1,28 @@
+pub mod animation;
+pub mod box_shape;
 pub mod camera;
+pub mod cone;
+pub mod constant_medium;
+pub mod csg;
+pub mod curve;
+pub mod cylinder;
+pub mod disk;
+pub mod displacement;
+pub mod heightfield;
+pub mod heterogeneous_medium;
+pub mod instance;
+pub mod mesh;
+pub mod metaball;
 pub mod moving_sphere;
+pub mod plane;
+pub mod rect;
+pub mod sdf;
 pub mod sphere;
+pub mod sphere_set;
+pub mod subdivision;
+pub mod torus;
+pub mod transform;
+pub mod triangle;
 
 use crate::aabb::AABB;
 use crate::materials::Material;
@@ -8,29 +30,84 @@ use crate::ray::Ray;
 
 use cgmath::prelude::*;
 use cgmath::vec3;
-use cgmath::Point3;
 use cgmath::Vector3;
 use rand::prelude::*;
 
 use std::f64::consts::PI;
 use std::sync::Arc;
 
+// How far past a crossing the search for the next one starts, so the same crossing is not
+// found twice
+const CROSSING_EPSILON: f64 = 0.0001;
+
 pub trait Hittable: Send + Sync {
     fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord>;
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB>;
+
+    // Every crossing of the surface between t_min and t_max, in order along the ray. Objects
+    // that can find all of their crossings at once may do better than repeated hits.
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        let mut crossings = Vec::new();
+        let mut t = t_min;
+        while let Some(hit) = self.hits(ray, t, t_max) {
+            t = hit.t + CROSSING_EPSILON;
+            crossings.push(hit);
+        }
+
+        crossings
+    }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Box<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        (**self).hits(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        (**self).bounding_box(t0, t1)
+    }
+
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        (**self).crossings(ray, t_min, t_max)
+    }
+}
+
+impl<T: Hittable + ?Sized> Hittable for Arc<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        (**self).hits(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        (**self).bounding_box(t0, t1)
+    }
+
+    fn crossings(&self, ray: &Ray, t_min: f64, t_max: f64) -> Vec<HitRecord> {
+        (**self).crossings(ray, t_min, t_max)
+    }
 }
 
 pub struct HitRecord {
     pub t: f64,
     pub p: Vector3<f64>,
+    // The normal used for shading, such as one interpolated from vertex normals. Like
+    // `geometric_normal` it points back to the side of the surface the ray came from.
     pub normal: Vector3<f64>,
+    // The normal of the surface itself
+    pub geometric_normal: Vector3<f64>,
+    // Whether the ray hit the outside of the surface, where its outward normal points
+    pub front_face: bool,
     pub material: Arc<Material>,
     pub u: f64,
     pub v: f64,
+    pub color: Option<Vector3<f64>>,
+    // Direction of increasing u, for shapes like curves whose materials depend on it
+    pub tangent: Option<Vector3<f64>>,
 }
 
 impl HitRecord {
+    // `normal` points out of the surface, and is turned around if the ray hit it from inside
     pub fn new(
+        ray: &Ray,
         t: f64,
         p: Vector3<f64>,
         normal: Vector3<f64>,
@@ -38,15 +115,27 @@ impl HitRecord {
         u: f64,
         v: f64,
     ) -> HitRecord {
+        let front_face = normal.dot(ray.direction) <= 0.0;
+        let normal = if front_face { normal } else { -normal };
+
         HitRecord {
             t,
             p,
             normal,
+            geometric_normal: normal,
+            front_face,
             material,
             u,
             v,
+            color: None,
+            tangent: None,
         }
     }
+
+    // Shades with an outward `normal` in place of the geometric one, turned to the same side
+    pub fn set_shading_normal(&mut self, normal: Vector3<f64>) {
+        self.normal = if self.front_face { normal } else { -normal };
+    }
 }
 
 #[derive(Default)]
@@ -101,35 +190,6 @@ impl Hittable for HittableList {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct TimedMovement {
-    pub starttime: f64,
-    pub start: Point3<f64>,
-    pub endtime: f64,
-    pub end: Point3<f64>,
-}
-
-impl TimedMovement {
-    pub fn new(
-        starttime: f64,
-        start: Point3<f64>,
-        endtime: f64,
-        end: Point3<f64>,
-    ) -> TimedMovement {
-        TimedMovement {
-            starttime,
-            start,
-            endtime,
-            end,
-        }
-    }
-
-    pub fn lerp(&self, time: f64) -> Vector3<f64> {
-        self.start.to_vec()
-            + ((time - self.starttime) / (self.endtime - time)) * (self.end - self.start)
-    }
-}
-
 fn random_in_unit_disk() -> Vector3<f64> {
     let mut rng = thread_rng();
     let mut p =
@@ -149,3 +209,13 @@ fn get_sphere_uv(p: Vector3<f64>) -> (f64, f64) {
 
     (u, v)
 }
+
+// Maps the angle of (x, z) around the y axis to [0, 1]
+fn get_angle_uv(x: f64, z: f64) -> f64 {
+    let phi = z.atan2(x);
+    if phi < 0.0 {
+        (phi + 2.0 * PI) / (2.0 * PI)
+    } else {
+        phi / (2.0 * PI)
+    }
+}
@@ -1,6 +1,6 @@
+use super::animation::Track;
 use super::HitRecord;
 use super::Hittable;
-use super::TimedMovement;
 use crate::aabb::AABB;
 use crate::materials::Material;
 use crate::objects::sphere::Sphere;
@@ -14,12 +14,21 @@ use cgmath::Vector3;
 use std::sync::Arc;
 
 pub struct MovingSphere {
-    pub movement: TimedMovement,
+    pub movement: Track<Vector3<f64>>,
     pub radius: f64,
     pub material: Arc<Material>,
 }
 
 impl MovingSphere {
+    pub fn new(movement: Track<Vector3<f64>>, radius: f64, material: Arc<Material>) -> Self {
+        MovingSphere {
+            movement,
+            radius,
+            material,
+        }
+    }
+
+    // Moves at a constant speed from `center0` at `time0` to `center1` at `time1`
     pub fn from(
         center0: Point3<f64>,
         center1: Point3<f64>,
@@ -28,22 +37,19 @@ impl MovingSphere {
         radius: f64,
         material: Arc<Material>,
     ) -> MovingSphere {
-        MovingSphere {
-            movement: TimedMovement::new(time0, center0, time1, center1),
-            radius,
-            material,
-        }
+        let movement = Track::linear(time0, center0.to_vec(), time1, center1.to_vec());
+        MovingSphere::new(movement, radius, material)
     }
 
     pub fn center(&self, time: f64) -> Vector3<f64> {
-        self.movement.lerp(time)
+        self.movement.sample(time)
     }
 }
 
 impl Hittable for MovingSphere {
     fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
-        let center = (self.movement.start.to_vec() + self.movement.end.to_vec()) / 2.0;
-        let oc = (ray.origin - self.center(ray.time)).to_vec();
+        let center = self.center(ray.time);
+        let oc = ray.origin - Point3::from_vec(center);
         let a = ray.direction.dot(ray.direction);
         let b = 2.0 * dot(oc, ray.direction);
         let c = dot(oc, oc) - self.radius.powi(2);
@@ -53,33 +59,37 @@ impl Hittable for MovingSphere {
             if t < t_max && t > t_min {
                 let point = ray.point_at(t);
                 let (u, v) = super::get_sphere_uv((point - center) / self.radius);
-                let normal = (point - self.center(ray.time)) / self.radius;
+                let normal = (point - center) / self.radius;
                 let material = Arc::clone(&self.material);
-                return Some(HitRecord::new(t, point, normal, material, u, v));
+                return Some(HitRecord::new(ray, t, point, normal, material, u, v));
             }
 
             let t = (-b + discriminant.sqrt()) / (2.0 * a);
             if t < t_max && t > t_min {
                 let point = ray.point_at(t);
                 let (u, v) = super::get_sphere_uv((point - center) / self.radius);
-                let normal = (point - self.center(ray.time)) / self.radius;
+                let normal = (point - center) / self.radius;
                 let material = Arc::clone(&self.material);
-                return Some(HitRecord::new(t, point, normal, material, u, v));
+                return Some(HitRecord::new(ray, t, point, normal, material, u, v));
             }
         }
 
         None
     }
 
+    // The center moves in straight lines between keyframes, so the spheres at the keyframes
+    // and at both ends of the interval cover it
     fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
-        let start_pos = Sphere::from(self.movement.start, self.radius, Arc::clone(&self.material));
-        let end_pos = Sphere::from(self.movement.end, self.radius, Arc::clone(&self.material));
-
-        let box_start = start_pos.bounding_box(t0, t1).unwrap();
-        let box_end = end_pos.bounding_box(t0, t1).unwrap();
-
-        let bx = box_start.surrounding_box(&box_end);
-
-        Some(bx)
+        self.movement
+            .times(t0, t1)
+            .into_iter()
+            .map(|time| {
+                let center = Point3::from_vec(self.center(time));
+                let sphere = Sphere::from(center, self.radius, Arc::clone(&self.material));
+                sphere.bounding_box(time, time).unwrap()
+            })
+            .fold(None, |bx: Option<AABB>, sphere| {
+                Some(bx.map_or(sphere, |bx| bx.surrounding_box(&sphere)))
+            })
     }
 }
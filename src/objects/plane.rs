@@ -0,0 +1,154 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::sync::Arc;
+
+// An infinite plane through `point`, facing along `normal`. It has no bounding box, so a
+// `BvhTree` keeps it out of the tree and tests it against every ray.
+pub struct Plane {
+    pub point: Point3<f64>,
+    pub normal: Vector3<f64>,
+    // Orthonormal axes in the plane that UVs are measured along
+    tangent: Vector3<f64>,
+    bitangent: Vector3<f64>,
+    material: Arc<Material>,
+}
+
+impl Plane {
+    pub fn new(point: Point3<f64>, normal: Vector3<f64>, material: Arc<Material>) -> Self {
+        let normal = normal.normalize();
+        let helper = if normal.x.abs() > 0.9 {
+            vec3(0.0, 1.0, 0.0)
+        } else {
+            vec3(1.0, 0.0, 0.0)
+        };
+        let bitangent = normal.cross(helper).normalize();
+        let tangent = bitangent.cross(normal);
+
+        Plane {
+            point,
+            normal,
+            tangent,
+            bitangent,
+            material,
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let denominator = self.normal.dot(ray.direction);
+        if denominator == 0.0 {
+            return None;
+        }
+
+        let t = (self.point - ray.origin).dot(self.normal) / denominator;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        // UVs are distances along the plane, so textures repeat every unit
+        let point = ray.point_at(t);
+        let offset = point - self.point.to_vec();
+        let u = offset.dot(self.tangent);
+        let v = offset.dot(self.bitangent);
+        let material = Arc::clone(&self.material);
+        Some(HitRecord::new(ray, t, point, self.normal, material, u, v))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bvh::BvhTree;
+    use crate::materials::lambertian::Lambertian;
+    use crate::objects::sphere::Sphere;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    fn ground() -> Plane {
+        Plane::new(Point3::new(0.0, 0.0, 0.0), vec3(0.0, 2.0, 0.0), material())
+    }
+
+    #[test]
+    fn hits_both_sides() {
+        let plane = ground();
+
+        let ray = Ray::from(Point3::new(1.0, 2.0, 3.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = plane.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.p, vec3(1.0, 0.0, 3.0));
+        assert_eq!(hit.normal, vec3(0.0, 1.0, 0.0));
+        assert!(hit.front_face);
+
+        let ray = Ray::from(Point3::new(0.0, -1.0, 0.0), vec3(1.0, 1.0, 0.0), 0.0);
+        let hit = plane.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.normal, vec3(0.0, -1.0, 0.0));
+        assert!(!hit.front_face);
+    }
+
+    #[test]
+    fn misses_parallel_and_distant_rays() {
+        let plane = ground();
+
+        let parallel = Ray::from(Point3::new(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(plane.hits(&parallel, 0.001, f64::MAX).is_none());
+        let away = Ray::from(Point3::new(0.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+        assert!(plane.hits(&away, 0.001, f64::MAX).is_none());
+        let down = Ray::from(Point3::new(0.0, 1.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert!(plane.hits(&down, 0.001, 0.5).is_none());
+    }
+
+    #[test]
+    fn stays_beside_the_bvh() {
+        let objects: Vec<Box<dyn Hittable>> = vec![
+            Box::new(ground()),
+            Box::new(Sphere::from(Point3::new(0.0, 1.0, 0.0), 0.5, material())),
+        ];
+        let world = BvhTree::new(objects, 0.0, 1.0);
+        assert!(world.bounding_box(0.0, 1.0).is_none());
+
+        // The sphere is in front of the plane for one ray and off to the side for the other
+        let ray = Ray::from(Point3::new(0.0, 3.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert_eq!(world.hits(&ray, 0.001, f64::MAX).unwrap().t, 1.5);
+        let ray = Ray::from(Point3::new(5.0, 3.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert_eq!(world.hits(&ray, 0.001, f64::MAX).unwrap().t, 3.0);
+    }
+
+    #[test]
+    fn builds_around_nan_bounds() {
+        let objects: Vec<Box<dyn Hittable>> = vec![
+            Box::new(Sphere::from(
+                Point3::new(f64::NAN, 0.0, 0.0),
+                1.0,
+                material(),
+            )),
+            Box::new(Sphere::from(Point3::new(0.0, 0.0, 0.0), 1.0, material())),
+            Box::new(Sphere::from(
+                Point3::new(0.0, f64::NAN, 0.0),
+                1.0,
+                material(),
+            )),
+            Box::new(Sphere::from(Point3::new(0.0, 0.0, 4.0), 1.0, material())),
+        ];
+        let world = BvhTree::new(objects, 0.0, 1.0);
+
+        let ray = Ray::from(Point3::new(0.0, 0.0, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert_eq!(world.hits(&ray, 0.001, f64::MAX).unwrap().t, 2.0);
+    }
+}
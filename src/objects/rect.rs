@@ -0,0 +1,231 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::vec3;
+
+use std::sync::Arc;
+
+// Axis-aligned rectangles are infinitely thin, so their boxes are padded along the
+// constant axis to keep the BVH slab test from missing them
+const THICKNESS: f64 = 0.0001;
+
+pub struct XYRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub y0: f64,
+    pub y1: f64,
+    pub k: f64,
+    material: Arc<Material>,
+}
+
+impl XYRect {
+    pub fn new(x0: f64, x1: f64, y0: f64, y1: f64, k: f64, material: Arc<Material>) -> Self {
+        XYRect {
+            x0,
+            x1,
+            y0,
+            y1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for XYRect {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = (self.k - ray.origin.z) / ray.direction.z;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let x = ray.origin.x + t * ray.direction.x;
+        let y = ray.origin.y + t * ray.direction.y;
+        if x < self.x0 || x > self.x1 || y < self.y0 || y > self.y1 {
+            return None;
+        }
+
+        let u = (x - self.x0) / (self.x1 - self.x0);
+        let v = (y - self.y0) / (self.y1 - self.y0);
+        let point = ray.point_at(t);
+        let normal = vec3(0.0, 0.0, 1.0);
+        let material = Arc::clone(&self.material);
+        Some(HitRecord::new(ray, t, point, normal, material, u, v))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let bx = AABB::new(
+            vec3(self.x0, self.y0, self.k),
+            vec3(self.x1, self.y1, self.k),
+        );
+        Some(bx.pad(THICKNESS))
+    }
+}
+
+pub struct XZRect {
+    pub x0: f64,
+    pub x1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    material: Arc<Material>,
+}
+
+impl XZRect {
+    pub fn new(x0: f64, x1: f64, z0: f64, z1: f64, k: f64, material: Arc<Material>) -> Self {
+        XZRect {
+            x0,
+            x1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for XZRect {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = (self.k - ray.origin.y) / ray.direction.y;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let x = ray.origin.x + t * ray.direction.x;
+        let z = ray.origin.z + t * ray.direction.z;
+        if x < self.x0 || x > self.x1 || z < self.z0 || z > self.z1 {
+            return None;
+        }
+
+        let u = (x - self.x0) / (self.x1 - self.x0);
+        let v = (z - self.z0) / (self.z1 - self.z0);
+        let point = ray.point_at(t);
+        let normal = vec3(0.0, 1.0, 0.0);
+        let material = Arc::clone(&self.material);
+        Some(HitRecord::new(ray, t, point, normal, material, u, v))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let bx = AABB::new(
+            vec3(self.x0, self.k, self.z0),
+            vec3(self.x1, self.k, self.z1),
+        );
+        Some(bx.pad(THICKNESS))
+    }
+}
+
+pub struct YZRect {
+    pub y0: f64,
+    pub y1: f64,
+    pub z0: f64,
+    pub z1: f64,
+    pub k: f64,
+    material: Arc<Material>,
+}
+
+impl YZRect {
+    pub fn new(y0: f64, y1: f64, z0: f64, z1: f64, k: f64, material: Arc<Material>) -> Self {
+        YZRect {
+            y0,
+            y1,
+            z0,
+            z1,
+            k,
+            material,
+        }
+    }
+}
+
+impl Hittable for YZRect {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let t = (self.k - ray.origin.x) / ray.direction.x;
+        if !(t > t_min && t < t_max) {
+            return None;
+        }
+
+        let y = ray.origin.y + t * ray.direction.y;
+        let z = ray.origin.z + t * ray.direction.z;
+        if y < self.y0 || y > self.y1 || z < self.z0 || z > self.z1 {
+            return None;
+        }
+
+        let u = (y - self.y0) / (self.y1 - self.y0);
+        let v = (z - self.z0) / (self.z1 - self.z0);
+        let point = ray.point_at(t);
+        let normal = vec3(1.0, 0.0, 0.0);
+        let material = Arc::clone(&self.material);
+        Some(HitRecord::new(ray, t, point, normal, material, u, v))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let bx = AABB::new(
+            vec3(self.k, self.y0, self.z0),
+            vec3(self.k, self.y1, self.z1),
+        );
+        Some(bx.pad(THICKNESS))
+    }
+}
+
+pub struct FlipNormals<T: Hittable> {
+    pub hittable: T,
+}
+
+impl<T: Hittable> FlipNormals<T> {
+    pub fn new(hittable: T) -> Self {
+        FlipNormals { hittable }
+    }
+}
+
+impl<T: Hittable> Hittable for FlipNormals<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let mut hit = self.hittable.hits(ray, t_min, t_max)?;
+        // The normal already faces the ray, only the side it hit changes
+        hit.front_face = !hit.front_face;
+        Some(hit)
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        self.hittable.bounding_box(t0, t1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    use cgmath::Point3;
+
+    #[test]
+    fn flips_the_side_but_not_the_normal() {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        let rect = XZRect::new(0.0, 1.0, 0.0, 1.0, 2.0, material);
+        let from_above = Ray::from(Point3::new(0.5, 3.0, 0.5), vec3(0.0, -1.0, 0.0), 0.0);
+        let from_below = Ray::from(Point3::new(0.5, 0.0, 0.5), vec3(0.0, 1.0, 0.0), 0.0);
+
+        let hit = rect.hits(&from_above, 0.001, f64::MAX).unwrap();
+        assert_eq!(
+            (hit.t, hit.normal, hit.front_face),
+            (1.0, vec3(0.0, 1.0, 0.0), true)
+        );
+        let hit = rect.hits(&from_below, 0.001, f64::MAX).unwrap();
+        assert_eq!(
+            (hit.t, hit.normal, hit.front_face),
+            (2.0, vec3(0.0, -1.0, 0.0), false)
+        );
+
+        let flipped = FlipNormals::new(rect);
+        let hit = flipped.hits(&from_above, 0.001, f64::MAX).unwrap();
+        assert_eq!(
+            (hit.t, hit.normal, hit.front_face),
+            (1.0, vec3(0.0, 1.0, 0.0), false)
+        );
+        let hit = flipped.hits(&from_below, 0.001, f64::MAX).unwrap();
+        assert_eq!(
+            (hit.t, hit.normal, hit.front_face),
+            (2.0, vec3(0.0, -1.0, 0.0), true)
+        );
+    }
+}
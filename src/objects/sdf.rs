@@ -0,0 +1,452 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec2;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::sync::Arc;
+
+const MAX_STEPS: usize = 512;
+const EPSILON: f64 = 1e-5;
+
+// A signed distance function: negative inside the surface and positive outside. Sphere tracing
+// steps by the returned distance, so it must never overestimate the distance to the surface.
+pub trait DistanceField: Send + Sync {
+    fn distance(&self, p: Point3<f64>) -> f64;
+    // Must enclose every point where the distance is negative
+    fn bounding_box(&self) -> AABB;
+}
+
+impl<T: DistanceField + ?Sized> DistanceField for Box<T> {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        (**self).distance(p)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        (**self).bounding_box()
+    }
+}
+
+impl<T: DistanceField + ?Sized> DistanceField for Arc<T> {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        (**self).distance(p)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        (**self).bounding_box()
+    }
+}
+
+// Renders the zero level set of a distance field by sphere tracing
+pub struct Sdf<T: DistanceField> {
+    pub field: T,
+    material: Arc<Material>,
+}
+
+impl<T: DistanceField> Sdf<T> {
+    pub fn new(field: T, material: Arc<Material>) -> Self {
+        Sdf { field, material }
+    }
+
+    // Central differences over the vertices of a tetrahedron, which only needs four samples
+    fn normal(&self, p: Point3<f64>) -> Vector3<f64> {
+        let h = EPSILON;
+        let offsets = [
+            vec3(1.0, -1.0, -1.0),
+            vec3(-1.0, -1.0, 1.0),
+            vec3(-1.0, 1.0, -1.0),
+            vec3(1.0, 1.0, 1.0),
+        ];
+
+        let gradient = offsets.iter().fold(Vector3::zero(), |sum, &k| {
+            sum + k * self.field.distance(p + k * h)
+        });
+        if gradient.magnitude2() > 0.0 {
+            gradient.normalize()
+        } else {
+            vec3(0.0, 1.0, 0.0)
+        }
+    }
+}
+
+impl<T: DistanceField> Hittable for Sdf<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (start, end) = self.field.bounding_box().interval(ray, t_min, t_max)?;
+        let length = ray.direction.magnitude();
+
+        // Rays entering the box start outside the surface. Rays starting within it may be inside,
+        // like refracted rays, and then march towards the exit. Rays leaving the surface start
+        // right on it, so they pick a side once they are clear of it.
+        let mut t = start;
+        let mut side = if start > t_min { 1.0 } else { 0.0 };
+        for _ in 0..MAX_STEPS {
+            let p = ray.origin + t * ray.direction;
+            let distance = self.field.distance(p);
+            if side == 0.0 {
+                if distance.abs() < EPSILON {
+                    t += EPSILON / length;
+                    continue;
+                }
+                side = distance.signum();
+            }
+
+            let distance = side * distance;
+            if distance < EPSILON {
+                let normal = self.normal(p);
+                let (u, v) = super::get_sphere_uv(normal);
+                let material = Arc::clone(&self.material);
+                return Some(HitRecord::new(ray, t, p.to_vec(), normal, material, u, v));
+            }
+
+            // Surfaces on the box itself are reached right at its end, so that step is still taken
+            t += distance / length;
+            if t > (end + EPSILON / length).min(t_max) {
+                break;
+            }
+        }
+
+        None
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(self.field.bounding_box())
+    }
+}
+
+// Wraps a closure as a distance field. `bounds` has to enclose the whole surface.
+pub struct FnField<F> {
+    function: F,
+    bounds: AABB,
+}
+
+impl<F: Fn(Point3<f64>) -> f64 + Send + Sync> FnField<F> {
+    pub fn new(function: F, bounds: AABB) -> Self {
+        FnField { function, bounds }
+    }
+}
+
+impl<F: Fn(Point3<f64>) -> f64 + Send + Sync> DistanceField for FnField<F> {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        (self.function)(p)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.bounds
+    }
+}
+
+// An axis aligned box spanning `half_extents` on either side of `center`
+pub struct BoxField {
+    pub center: Point3<f64>,
+    pub half_extents: Vector3<f64>,
+}
+
+impl BoxField {
+    pub fn new(center: Point3<f64>, half_extents: Vector3<f64>) -> Self {
+        BoxField {
+            center,
+            half_extents,
+        }
+    }
+}
+
+impl DistanceField for BoxField {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        box_distance(p - self.center, self.half_extents)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        AABB::new(
+            self.center.to_vec() - self.half_extents,
+            self.center.to_vec() + self.half_extents,
+        )
+    }
+}
+
+// A box of the same size as `BoxField` with its edges rounded off by `radius`
+pub struct RoundBoxField {
+    pub center: Point3<f64>,
+    pub half_extents: Vector3<f64>,
+    pub radius: f64,
+}
+
+impl RoundBoxField {
+    pub fn new(center: Point3<f64>, half_extents: Vector3<f64>, radius: f64) -> Self {
+        RoundBoxField {
+            center,
+            half_extents,
+            radius,
+        }
+    }
+}
+
+impl DistanceField for RoundBoxField {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        let r = self.radius;
+        box_distance(p - self.center, self.half_extents - vec3(r, r, r)) - r
+    }
+
+    fn bounding_box(&self) -> AABB {
+        AABB::new(
+            self.center.to_vec() - self.half_extents,
+            self.center.to_vec() + self.half_extents,
+        )
+    }
+}
+
+// All points within `radius` of the segment from `a` to `b`
+pub struct CapsuleField {
+    pub a: Point3<f64>,
+    pub b: Point3<f64>,
+    pub radius: f64,
+}
+
+impl CapsuleField {
+    pub fn new(a: Point3<f64>, b: Point3<f64>, radius: f64) -> Self {
+        CapsuleField { a, b, radius }
+    }
+}
+
+impl DistanceField for CapsuleField {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        let pa = p - self.a;
+        let ba = self.b - self.a;
+        let h = if ba.magnitude2() > 0.0 {
+            (pa.dot(ba) / ba.magnitude2()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        (pa - ba * h).magnitude() - self.radius
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let r = vec3(self.radius, self.radius, self.radius);
+        let min = vec3(
+            self.a.x.min(self.b.x),
+            self.a.y.min(self.b.y),
+            self.a.z.min(self.b.z),
+        );
+        let max = vec3(
+            self.a.x.max(self.b.x),
+            self.a.y.max(self.b.y),
+            self.a.z.max(self.b.z),
+        );
+
+        AABB::new(min - r, max + r)
+    }
+}
+
+// The same ring as `Torus`, around the y axis through `center`
+pub struct TorusField {
+    pub center: Point3<f64>,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+}
+
+impl TorusField {
+    pub fn new(center: Point3<f64>, major_radius: f64, minor_radius: f64) -> Self {
+        TorusField {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+impl DistanceField for TorusField {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        let p = p - self.center;
+        let q = vec2(vec2(p.x, p.z).magnitude() - self.major_radius, p.y);
+        q.magnitude() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let extent = self.major_radius + self.minor_radius;
+        AABB::new(
+            self.center.to_vec() - vec3(extent, self.minor_radius, extent),
+            self.center.to_vec() + vec3(extent, self.minor_radius, extent),
+        )
+    }
+}
+
+// Joins two fields, blending them together where they come within `k` of each other
+// Refer: https://iquilezles.org/articles/smin/
+pub struct SmoothUnion<A: DistanceField, B: DistanceField> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+impl<A: DistanceField, B: DistanceField> SmoothUnion<A, B> {
+    pub fn new(a: A, b: B, k: f64) -> Self {
+        SmoothUnion { a, b, k }
+    }
+}
+
+impl<A: DistanceField, B: DistanceField> DistanceField for SmoothUnion<A, B> {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        let (a, b) = (self.a.distance(p), self.b.distance(p));
+        a.min(b) - blend(a, b, self.k)
+    }
+
+    fn bounding_box(&self) -> AABB {
+        // The blend pulls the surface out by at most k / 4
+        let grow = vec3(self.k, self.k, self.k) / 4.0;
+        let bx = self
+            .a
+            .bounding_box()
+            .surrounding_box(&self.b.bounding_box());
+        AABB::new(bx.min - grow, bx.max + grow)
+    }
+}
+
+// Carves `b` out of `a`, rounding the cut edges over a distance of `k`
+pub struct SmoothSubtraction<A: DistanceField, B: DistanceField> {
+    pub a: A,
+    pub b: B,
+    pub k: f64,
+}
+
+impl<A: DistanceField, B: DistanceField> SmoothSubtraction<A, B> {
+    pub fn new(a: A, b: B, k: f64) -> Self {
+        SmoothSubtraction { a, b, k }
+    }
+}
+
+impl<A: DistanceField, B: DistanceField> DistanceField for SmoothSubtraction<A, B> {
+    fn distance(&self, p: Point3<f64>) -> f64 {
+        let (a, b) = (self.a.distance(p), -self.b.distance(p));
+        a.max(b) + blend(a, b, self.k)
+    }
+
+    // Subtracting can only shrink `a`
+    fn bounding_box(&self) -> AABB {
+        self.a.bounding_box()
+    }
+}
+
+fn blend(a: f64, b: f64, k: f64) -> f64 {
+    if k <= 0.0 {
+        return 0.0;
+    }
+
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    h * h * k / 4.0
+}
+
+fn box_distance(p: Vector3<f64>, half_extents: Vector3<f64>) -> f64 {
+    let q = vec3(p.x.abs(), p.y.abs(), p.z.abs()) - half_extents;
+    let outside = vec3(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+    let inside = q.x.max(q.y).max(q.z).min(0.0);
+
+    outside + inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    fn assert_hit(hit: Option<HitRecord>, t: f64, normal: Vector3<f64>) {
+        let hit = hit.expect("missed the surface");
+        assert!((hit.t - t).abs() < 1e-4, "t = {}", hit.t);
+        assert!((hit.normal - normal).magnitude() < 1e-3, "{:?}", hit.normal);
+    }
+
+    #[test]
+    fn traces_boxes() {
+        let sdf = Sdf::new(
+            BoxField::new(Point3::new(0.0, 0.0, 0.0), vec3(1.0, 0.5, 2.0)),
+            material(),
+        );
+
+        let ray = Ray::from(Point3::new(-5.0, 0.2, 0.3), vec3(2.0, 0.0, 0.0), 0.0);
+        assert_hit(
+            sdf.hits(&ray, 0.0, f64::INFINITY),
+            2.0,
+            vec3(-1.0, 0.0, 0.0),
+        );
+        let ray = Ray::from(Point3::new(0.3, 5.0, 0.1), vec3(0.0, -1.0, 0.0), 0.0);
+        assert_hit(sdf.hits(&ray, 0.0, f64::INFINITY), 4.5, vec3(0.0, 1.0, 0.0));
+        assert!(sdf.hits(&ray, 0.0, 4.4).is_none());
+        let ray = Ray::from(Point3::new(1.1, 5.0, 0.1), vec3(0.0, -1.0, 0.0), 0.0);
+        assert!(sdf.hits(&ray, 0.0, f64::INFINITY).is_none());
+
+        // From inside, like a refracted ray, the exit faces back at the ray
+        let ray = Ray::from(Point3::new(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let hit = sdf.hits(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(!hit.front_face);
+        assert_hit(Some(hit), 2.0, vec3(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn traces_capsules() {
+        let sdf = Sdf::new(
+            CapsuleField::new(Point3::new(0.0, -1.0, 0.0), Point3::new(0.0, 1.0, 0.0), 0.5),
+            material(),
+        );
+
+        let ray = Ray::from(Point3::new(-3.0, 0.5, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert_hit(
+            sdf.hits(&ray, 0.0, f64::INFINITY),
+            2.5,
+            vec3(-1.0, 0.0, 0.0),
+        );
+        let ray = Ray::from(Point3::new(0.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert_hit(sdf.hits(&ray, 0.0, f64::INFINITY), 3.5, vec3(0.0, 1.0, 0.0));
+
+        // Off center the cap is hit where it is tilted towards x
+        let ray = Ray::from(Point3::new(0.3, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert_hit(sdf.hits(&ray, 0.0, f64::INFINITY), 3.6, vec3(0.6, 0.8, 0.0));
+        let ray = Ray::from(Point3::new(0.45, 1.45, -3.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert!(sdf.hits(&ray, 0.0, f64::INFINITY).is_none());
+    }
+
+    #[test]
+    fn bounds_the_blended_surface() {
+        let field = SmoothUnion::new(
+            BoxField::new(Point3::new(-1.0, 0.0, 0.0), vec3(1.0, 1.0, 1.0)),
+            BoxField::new(Point3::new(1.1, 0.0, 0.0), vec3(1.0, 1.0, 1.0)),
+            1.0,
+        );
+        let bounds = field.bounding_box();
+        let children = field
+            .a
+            .bounding_box()
+            .surrounding_box(&field.b.bounding_box());
+
+        // The blend fills in the gap between the boxes and swells past their sides there
+        let mut swollen = false;
+        for i in -60..=60 {
+            for j in -40..=40 {
+                for k in -40..=40 {
+                    let p = Point3::new(f64::from(i), f64::from(j), f64::from(k)) * 0.04;
+                    if field.distance(p) >= 0.0 {
+                        continue;
+                    }
+                    for axis in 0..3 {
+                        assert!(bounds.min[axis] <= p[axis] && p[axis] <= bounds.max[axis]);
+                        swollen |= p[axis] > children.max[axis];
+                    }
+                }
+            }
+        }
+        assert!(swollen);
+
+        let sdf = Sdf::new(field, material());
+        let ray = Ray::from(Point3::new(0.05, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        let hit = sdf.hits(&ray, 0.0, f64::INFINITY).unwrap();
+        assert!(hit.p.y > 1.0 && hit.p.y < bounds.max.y);
+    }
+}
@@ -41,7 +41,7 @@ impl Hittable for Sphere {
                 let (u, v) = super::get_sphere_uv((point - self.center.to_vec()) / self.radius);
                 let normal = (point - self.center.to_vec()) / self.radius;
                 let material = Arc::clone(&self.material);
-                return Some(HitRecord::new(t, point, normal, material, u, v));
+                return Some(HitRecord::new(ray, t, point, normal, material, u, v));
             }
 
             let t = (-b + discriminant.sqrt()) / (2.0 * a);
@@ -50,7 +50,7 @@ impl Hittable for Sphere {
                 let (u, v) = super::get_sphere_uv((point - self.center.to_vec()) / self.radius);
                 let normal = (point - self.center.to_vec()) / self.radius;
                 let material = Arc::clone(&self.material);
-                return Some(HitRecord::new(t, point, normal, material, u, v));
+                return Some(HitRecord::new(ray, t, point, normal, material, u, v));
             }
         }
 
@@ -64,3 +64,28 @@ impl Hittable for Sphere {
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    #[test]
+    fn faces_the_ray_from_either_side() {
+        let material = Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)));
+        let sphere = Sphere::from(Point3::new(0.0, 0.0, 0.0), 2.0, material);
+
+        let ray = Ray::from(Point3::new(0.0, 0.0, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let hit = sphere.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 3.0);
+        assert_eq!(hit.normal, vec3(0.0, 0.0, -1.0));
+        assert_eq!(hit.geometric_normal, hit.normal);
+        assert!(hit.front_face);
+
+        let ray = Ray::from(Point3::new(0.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0), 0.0);
+        let hit = sphere.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_eq!(hit.t, 1.0);
+        assert_eq!(hit.normal, vec3(0.0, -1.0, 0.0));
+        assert!(!hit.front_face);
+    }
+}
@@ -0,0 +1,446 @@
+use super::get_sphere_uv;
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::f64;
+use std::mem::swap;
+use std::sync::Arc;
+
+// Up to this many spheres share a leaf of the tree
+const LEAF_SIZE: usize = 4;
+// Deep enough for a tree over any number of spheres a u32 can index
+const STACK_SIZE: usize = 64;
+
+// Many spheres with the same few materials, such as the particles of a simulation. Centers,
+// radii and material indices sit in flat arrays in single precision, next to a BVH of their own,
+// which takes around 40 bytes per sphere against a few hundred for boxed `Sphere`s in a BvhTree.
+pub struct SphereSet {
+    // Sorted so the spheres under each leaf are next to each other
+    centers: Vec<Point3<f32>>,
+    radii: Vec<f32>,
+    material_ids: Vec<u32>,
+    materials: Vec<Arc<Material>>,
+    nodes: Vec<Node>,
+}
+
+// The first child of an inner node directly follows it
+struct Node {
+    min: [f32; 3],
+    max: [f32; 3],
+    // First sphere of a leaf, or the second child of an inner node
+    offset: u32,
+    // Spheres in a leaf, 0 for inner nodes
+    count: u32,
+}
+
+impl SphereSet {
+    // `material_ids` index into `materials`, one for each sphere
+    pub fn new(
+        centers: Vec<Point3<f32>>,
+        radii: Vec<f32>,
+        material_ids: Vec<u32>,
+        materials: Vec<Arc<Material>>,
+    ) -> Self {
+        assert!(
+            centers.len() == radii.len() && centers.len() == material_ids.len(),
+            "SphereSet needs a radius and a material for every center"
+        );
+        assert!(
+            material_ids
+                .iter()
+                .all(|&id| (id as usize) < materials.len()),
+            "SphereSet material index out of range"
+        );
+        assert!(
+            centers.len() <= u32::MAX as usize,
+            "SphereSet holds at most u32::MAX spheres"
+        );
+
+        let mut set = SphereSet {
+            centers,
+            radii,
+            material_ids,
+            materials,
+            nodes: Vec::new(),
+        };
+        set.build();
+        set
+    }
+
+    pub fn len(&self) -> usize {
+        self.centers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centers.is_empty()
+    }
+
+    fn build(&mut self) {
+        if self.is_empty() {
+            return;
+        }
+
+        let mut order: Vec<u32> = (0..self.len() as u32).collect();
+        self.nodes.reserve(2 * self.len() / LEAF_SIZE + 1);
+        self.build_node(&mut order, 0);
+
+        self.centers = order.iter().map(|&i| self.centers[i as usize]).collect();
+        self.radii = order.iter().map(|&i| self.radii[i as usize]).collect();
+        self.material_ids = order
+            .iter()
+            .map(|&i| self.material_ids[i as usize])
+            .collect();
+    }
+
+    // Splits at the median center along the longest side of the box around the centers, and
+    // returns the bounds of the node
+    fn build_node(&mut self, order: &mut [u32], offset: usize) -> ([f32; 3], [f32; 3]) {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            min: [0.0; 3],
+            max: [0.0; 3],
+            offset: offset as u32,
+            count: order.len() as u32,
+        });
+
+        let (min, max) = if order.len() <= LEAF_SIZE {
+            let mut min = [f32::INFINITY; 3];
+            let mut max = [f32::NEG_INFINITY; 3];
+            for &i in order.iter() {
+                let (center, radius) = (self.centers[i as usize], self.radii[i as usize]);
+                for a in 0..3 {
+                    min[a] = min[a].min(round_down(f64::from(center[a]) - f64::from(radius)));
+                    max[a] = max[a].max(round_up(f64::from(center[a]) + f64::from(radius)));
+                }
+            }
+            (min, max)
+        } else {
+            let mut center_min = [f32::INFINITY; 3];
+            let mut center_max = [f32::NEG_INFINITY; 3];
+            for &i in order.iter() {
+                let center = self.centers[i as usize];
+                for a in 0..3 {
+                    center_min[a] = center_min[a].min(center[a]);
+                    center_max[a] = center_max[a].max(center[a]);
+                }
+            }
+
+            let extent = |a: usize| center_max[a] - center_min[a];
+            let axis = (0..3)
+                .max_by(|&a, &b| extent(a).total_cmp(&extent(b)))
+                .unwrap();
+            let half = order.len() / 2;
+            let centers = &self.centers;
+            // NaN centers, which particle caches can hold, are sorted to one end rather than
+            // panicking
+            order.select_nth_unstable_by(half, |&i, &j| {
+                let (a, b) = (centers[i as usize][axis], centers[j as usize][axis]);
+                a.total_cmp(&b)
+            });
+
+            let (left, right) = order.split_at_mut(half);
+            let (left_min, left_max) = self.build_node(left, offset);
+            self.nodes[index].offset = self.nodes.len() as u32;
+            self.nodes[index].count = 0;
+            let (right_min, right_max) = self.build_node(right, offset + half);
+
+            let mut min = left_min;
+            let mut max = left_max;
+            for a in 0..3 {
+                min[a] = min[a].min(right_min[a]);
+                max[a] = max[a].max(right_max[a]);
+            }
+            (min, max)
+        };
+
+        self.nodes[index].min = min;
+        self.nodes[index].max = max;
+        (min, max)
+    }
+
+    // Where the ray enters the box of a node, if it does before `t_max`
+    fn enter(
+        &self,
+        node: &Node,
+        ray: &Ray,
+        inverse: Vector3<f64>,
+        t_min: f64,
+        t_max: f64,
+    ) -> Option<f64> {
+        let (mut t0, mut t1) = (t_min, t_max);
+        for a in 0..3 {
+            let mut near = (f64::from(node.min[a]) - ray.origin[a]) * inverse[a];
+            let mut far = (f64::from(node.max[a]) - ray.origin[a]) * inverse[a];
+            if inverse[a] < 0.0 {
+                swap(&mut near, &mut far);
+            }
+            t0 = t0.max(near);
+            t1 = t1.min(far);
+            if t1 <= t0 {
+                return None;
+            }
+        }
+
+        Some(t0)
+    }
+
+    fn intersect(&self, i: usize, ray: &Ray, t_min: f64, t_max: f64) -> Option<f64> {
+        let c = self.centers[i];
+        let radius = f64::from(self.radii[i]);
+        let oc = ray.origin - Point3::new(f64::from(c.x), f64::from(c.y), f64::from(c.z));
+        let a = ray.direction.magnitude2();
+        let half_b = oc.dot(ray.direction);
+        let c = oc.magnitude2() - radius * radius;
+        let discriminant = half_b * half_b - a * c;
+        if discriminant <= 0.0 {
+            return None;
+        }
+
+        let root = discriminant.sqrt();
+        [(-half_b - root) / a, (-half_b + root) / a]
+            .iter()
+            .cloned()
+            .find(|&t| t > t_min && t < t_max)
+    }
+}
+
+impl Hittable for SphereSet {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let inverse = vec3(
+            1.0 / ray.direction.x,
+            1.0 / ray.direction.y,
+            1.0 / ray.direction.z,
+        );
+        let mut closest = t_max;
+        let mut hit = None;
+
+        let mut stack = [0u32; STACK_SIZE];
+        let mut depth = 1;
+        while depth > 0 {
+            depth -= 1;
+            let node = &self.nodes[stack[depth] as usize];
+            if self.enter(node, ray, inverse, t_min, closest).is_none() {
+                continue;
+            }
+
+            if node.count > 0 {
+                let start = node.offset as usize;
+                for i in start..start + node.count as usize {
+                    if let Some(t) = self.intersect(i, ray, t_min, closest) {
+                        closest = t;
+                        hit = Some(i);
+                    }
+                }
+                continue;
+            }
+
+            // The nearer child goes on top of the stack so it is searched first
+            let first = stack[depth] + 1;
+            let second = node.offset;
+            let t_first = self.enter(&self.nodes[first as usize], ray, inverse, t_min, closest);
+            let t_second = self.enter(&self.nodes[second as usize], ray, inverse, t_min, closest);
+            let (near, far) = match (t_first, t_second) {
+                (Some(a), Some(b)) if b < a => (Some(second), Some(first)),
+                (Some(_), Some(_)) => (Some(first), Some(second)),
+                (Some(_), None) => (Some(first), None),
+                (None, Some(_)) => (Some(second), None),
+                (None, None) => (None, None),
+            };
+            for child in far.into_iter().chain(near) {
+                stack[depth] = child;
+                depth += 1;
+            }
+        }
+
+        let i = hit?;
+        let point = ray.point_at(closest);
+        let c = self.centers[i];
+        let center = vec3(f64::from(c.x), f64::from(c.y), f64::from(c.z));
+        let normal = (point - center) / f64::from(self.radii[i]);
+        let (u, v) = get_sphere_uv(normal);
+        let material = Arc::clone(&self.materials[self.material_ids[i] as usize]);
+
+        Some(HitRecord::new(ray, closest, point, normal, material, u, v))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        self.nodes.first().map(|root| {
+            AABB::new(
+                vec3(
+                    f64::from(root.min[0]),
+                    f64::from(root.min[1]),
+                    f64::from(root.min[2]),
+                ),
+                vec3(
+                    f64::from(root.max[0]),
+                    f64::from(root.max[1]),
+                    f64::from(root.max[2]),
+                ),
+            )
+        })
+    }
+}
+
+// Single precision bounds that still contain the double precision value
+fn round_down(x: f64) -> f32 {
+    let y = x as f32;
+    if f64::from(y) > x {
+        previous(y)
+    } else {
+        y
+    }
+}
+
+fn round_up(x: f64) -> f32 {
+    let y = x as f32;
+    if f64::from(y) < x {
+        -previous(-y)
+    } else {
+        y
+    }
+}
+
+// The next f32 toward negative infinity, as f32::next_down finds from Rust 1.86 on
+fn previous(y: f32) -> f32 {
+    if y == 0.0 {
+        -f32::from_bits(1)
+    } else if y > 0.0 {
+        f32::from_bits(y.to_bits() - 1)
+    } else {
+        f32::from_bits(y.to_bits() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::objects::sphere::Sphere;
+    use crate::objects::HittableList;
+
+    // A grid of spheres with a few shared materials, next to the same spheres as `Sphere`s
+    fn scene() -> (SphereSet, HittableList) {
+        let materials: Vec<Arc<Material>> = (0..3)
+            .map(|i| {
+                let shade = f64::from(i) / 3.0;
+                Arc::new(Material::Lambertian(Lambertian::color(shade, shade, shade)))
+            })
+            .collect();
+
+        let mut centers = Vec::new();
+        let mut radii = Vec::new();
+        let mut material_ids = Vec::new();
+        let mut list = HittableList::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                let center = Point3::new(i as f32 - 4.5, (i * j % 3) as f32 * 0.5, j as f32 - 4.5);
+                let radius = 0.25 + (i + j) as f32 % 4.0 * 0.0625;
+                let id = (i + 2 * j) % 3;
+                list.add(Box::new(Sphere::from(
+                    center.cast().unwrap(),
+                    f64::from(radius),
+                    Arc::clone(&materials[id as usize]),
+                )));
+                centers.push(center);
+                radii.push(radius);
+                material_ids.push(id);
+            }
+        }
+
+        (
+            SphereSet::new(centers, radii, material_ids, materials),
+            list,
+        )
+    }
+
+    #[test]
+    fn matches_separate_spheres() {
+        let (set, list) = scene();
+        assert_eq!(set.len(), 100);
+
+        let mut hits = 0;
+        for i in 0..40 {
+            for j in 0..40 {
+                let origin = Point3::new(-7.0, 6.0, -7.0);
+                let target = vec3(f64::from(i) * 0.3 - 6.0, 0.0, f64::from(j) * 0.3 - 6.0);
+                let ray = Ray::from(origin, target - origin.to_vec(), 0.0);
+
+                let expected = list.hits(&ray, 0.001, f64::MAX);
+                let hit = set.hits(&ray, 0.001, f64::MAX);
+                assert_eq!(hit.is_some(), expected.is_some());
+                if let (Some(hit), Some(expected)) = (hit, expected) {
+                    assert!((hit.t - expected.t).abs() < 1e-9);
+                    assert!((hit.normal - expected.normal).magnitude() < 1e-9);
+                    assert!(Arc::ptr_eq(&hit.material, &expected.material));
+                    hits += 1;
+                }
+            }
+        }
+        assert!(hits > 100);
+    }
+
+    #[test]
+    fn bounds_every_sphere() {
+        let (set, list) = scene();
+        let bx = set.bounding_box(0.0, 1.0).unwrap();
+        let expected = list.bounding_box(0.0, 1.0).unwrap();
+        for a in 0..3 {
+            assert!(bx.min[a] <= expected.min[a] && bx.max[a] >= expected.max[a]);
+        }
+
+        let empty = SphereSet::new(Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        let ray = Ray::from(Point3::new(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(empty.hits(&ray, 0.001, f64::MAX).is_none());
+        assert!(empty.bounding_box(0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn rounds_outwards_to_single_precision() {
+        for &x in &[0.1, -0.1, 1.0, -3.7e-39, 1e-46, -1e-46, 0.0, 3.4e38, 1e39] {
+            let (lo, hi) = (round_down(x), round_up(x));
+            assert!(
+                f64::from(lo) <= x && x <= f64::from(hi),
+                "{} not in [{}, {}]",
+                x,
+                lo,
+                hi
+            );
+            assert!(
+                lo == hi || previous(hi) == lo,
+                "[{}, {}] is not tight",
+                lo,
+                hi
+            );
+        }
+    }
+
+    #[test]
+    fn builds_around_nan_spheres() {
+        let materials = vec![Arc::new(Material::Lambertian(Lambertian::color(
+            0.5, 0.5, 0.5,
+        )))];
+        let mut centers = Vec::new();
+        let mut radii = Vec::new();
+        for i in 0..20 {
+            let x = if i % 3 == 0 { f32::NAN } else { i as f32 * 3.0 };
+            centers.push(Point3::new(x, 0.0, 0.0));
+            radii.push(if i % 5 == 0 { f32::NAN } else { 1.0 });
+        }
+        let set = SphereSet::new(centers, radii, vec![0; 20], materials);
+
+        let ray = Ray::from(Point3::new(6.0, 0.0, -5.0), vec3(0.0, 0.0, 1.0), 0.0);
+        assert_eq!(set.hits(&ray, 0.001, f64::MAX).unwrap().t, 4.0);
+    }
+}
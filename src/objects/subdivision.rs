@@ -0,0 +1,682 @@
+use super::mesh::position_key;
+use super::mesh::TriangleMesh;
+use crate::materials::Material;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::collections::HashMap;
+use std::f64;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SubdivisionScheme {
+    // Suits quad cages. Every polygon turns into quads after the first level.
+    CatmullClark,
+    // Suits triangle cages. Other polygons are split into triangles first.
+    Loop,
+}
+
+// The control cage of a subdivision surface. UVs are indexed per face corner separately from
+// the positions, so a UV seam can split the texture where the surface itself is connected.
+pub struct PolygonMesh {
+    pub positions: Vec<Point3<f64>>,
+    pub faces: Vec<Vec<u32>>,
+    pub uvs: Option<Vec<(f64, f64)>>,
+    // Indices into `uvs` for the corners of each face, when there are UVs
+    pub uv_faces: Vec<Vec<u32>>,
+    // Sharpness of tagged edges, keyed by their end points with the smaller index first. An
+    // edge stays sharp for as many levels as its sharpness and then blends into the surface.
+    pub creases: HashMap<(u32, u32), f64>,
+    pub face_materials: Vec<u32>,
+    pub materials: Vec<Arc<Material>>,
+}
+
+impl PolygonMesh {
+    pub fn new(material: Arc<Material>) -> Self {
+        PolygonMesh {
+            positions: Vec::new(),
+            faces: Vec::new(),
+            uvs: None,
+            uv_faces: Vec::new(),
+            creases: HashMap::new(),
+            face_materials: Vec::new(),
+            materials: vec![material],
+        }
+    }
+
+    // Welds the vertices of a triangle mesh that share a position, which loaders split along
+    // UV seams and hard edges
+    pub fn from_triangles(mesh: &TriangleMesh) -> Self {
+        let mut welded: HashMap<[u64; 3], u32> = HashMap::new();
+        let mut positions = Vec::new();
+        let remap: Vec<u32> = mesh
+            .positions
+            .iter()
+            .map(|p| {
+                let next = positions.len() as u32;
+                let index = *welded.entry(position_key(p)).or_insert(next);
+                if index == next {
+                    positions.push(*p);
+                }
+                index
+            })
+            .collect();
+
+        let faces = mesh
+            .indices
+            .iter()
+            .map(|face| face.iter().map(|&i| remap[i as usize]).collect())
+            .collect();
+        let uv_faces = match mesh.uvs {
+            Some(_) => mesh.indices.iter().map(|face| face.to_vec()).collect(),
+            None => Vec::new(),
+        };
+
+        PolygonMesh {
+            positions,
+            faces,
+            uvs: mesh.uvs.clone(),
+            uv_faces,
+            creases: HashMap::new(),
+            face_materials: mesh.face_materials.clone(),
+            materials: mesh.materials.clone(),
+        }
+    }
+
+    pub fn add_face(&mut self, vertices: Vec<u32>, material: u32) {
+        self.faces.push(vertices);
+        self.face_materials.push(material);
+    }
+
+    pub fn set_crease(&mut self, a: u32, b: u32, sharpness: f64) {
+        self.creases.insert(edge_key(a, b), sharpness);
+    }
+
+    pub fn len(&self) -> usize {
+        self.faces.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faces.is_empty()
+    }
+
+    // Refines the cage `levels` times. The UVs are refined with the same scheme, where UV seams
+    // are treated as boundaries.
+    pub fn subdivide(&self, scheme: SubdivisionScheme, levels: u32) -> PolygonMesh {
+        // Faces that repeat a vertex, like "f 1 1 2", have no area and leave edges without two
+        // distinct sides, so they are dropped
+        let keep: Vec<bool> = self
+            .faces
+            .iter()
+            .map(|face| {
+                face.len() >= 3 && face.iter().enumerate().all(|(k, v)| !face[..k].contains(v))
+            })
+            .collect();
+        let kept = |faces: &[Vec<u32>]| -> Vec<Vec<u32>> {
+            faces
+                .iter()
+                .zip(&keep)
+                .filter(|(_, &keep)| keep)
+                .map(|(face, _)| face.clone())
+                .collect()
+        };
+
+        let mut positions = Cage {
+            points: self.positions.iter().map(|p| p.to_vec()).collect(),
+            faces: kept(&self.faces),
+            creases: self.creases.clone(),
+        };
+        let mut uvs = self.uvs.as_ref().map(|uvs| Cage {
+            points: uvs.iter().map(|&(u, v)| vec3(u, v, 0.0)).collect(),
+            faces: kept(&self.uv_faces),
+            creases: HashMap::new(),
+        });
+        let mut face_materials: Vec<u32> = self
+            .face_materials
+            .iter()
+            .zip(&keep)
+            .filter(|(_, &keep)| keep)
+            .map(|(&material, _)| material)
+            .collect();
+
+        if scheme == SubdivisionScheme::Loop && levels > 0 {
+            face_materials = expand(&positions.faces, &face_materials, |face| face.len() - 2);
+            positions = positions.triangulate();
+            uvs = uvs.map(|uvs| uvs.triangulate());
+        }
+
+        for _ in 0..levels {
+            face_materials = expand(&positions.faces, &face_materials, |face| match scheme {
+                SubdivisionScheme::CatmullClark => face.len(),
+                SubdivisionScheme::Loop => 4,
+            });
+            positions = positions.refine(scheme);
+            uvs = uvs.map(|uvs| uvs.refine(scheme));
+        }
+
+        let (uvs, uv_faces) = match uvs {
+            Some(uvs) => (
+                Some(uvs.points.iter().map(|p| (p.x, p.y)).collect()),
+                uvs.faces,
+            ),
+            None => (None, Vec::new()),
+        };
+
+        PolygonMesh {
+            positions: positions.points.into_iter().map(Point3::from_vec).collect(),
+            faces: positions.faces,
+            uvs,
+            uv_faces,
+            creases: positions.creases,
+            face_materials,
+            materials: self.materials.clone(),
+        }
+    }
+
+    // Splits the faces into triangles. Normals are averaged over the faces around each vertex,
+    // except across boundaries and edges that are still sharp, which keep a hard edge.
+    pub fn to_triangle_mesh(&self) -> TriangleMesh {
+        let topology = Topology::new(self.positions.len(), &self.faces, &self.creases);
+
+        let face_normals: Vec<Vector3<f64>> = self
+            .faces
+            .iter()
+            .map(|face| {
+                let p0 = self.positions[face[0] as usize];
+                (1..face.len() - 1).fold(Vector3::zero(), |normal, k| {
+                    let p1 = self.positions[face[k] as usize];
+                    let p2 = self.positions[face[k + 1] as usize];
+                    normal + (p1 - p0).cross(p2 - p0)
+                })
+            })
+            .collect();
+
+        // Corners of neighbouring faces around a vertex are joined unless the edge is sharp
+        let mut first_corner = Vec::with_capacity(self.faces.len());
+        let mut corners = 0;
+        for face in &self.faces {
+            first_corner.push(corners);
+            corners += face.len();
+        }
+        let corner = |face: usize, vertex: u32| {
+            first_corner[face] + self.faces[face].iter().position(|&v| v == vertex).unwrap()
+        };
+        let mut groups: Vec<usize> = (0..corners).collect();
+        for edge in &topology.edges {
+            if edge.sharpness > 0.0 {
+                continue;
+            }
+            let (f0, f1) = (edge.faces[0], edge.faces[1]);
+            for &vertex in &edge.ends {
+                let a = find(&mut groups, corner(f0, vertex));
+                let b = find(&mut groups, corner(f1, vertex));
+                groups[a] = b;
+            }
+        }
+
+        let mut group_normals = vec![Vector3::zero(); corners];
+        for (f, face) in self.faces.iter().enumerate() {
+            for k in 0..face.len() {
+                let group = find(&mut groups, first_corner[f] + k);
+                group_normals[group] += face_normals[f];
+            }
+        }
+
+        let mut mesh = TriangleMesh::new(Arc::clone(&self.materials[0]));
+        mesh.materials = self.materials.clone();
+        let mut normals = Vec::new();
+        let mut uvs = self.uvs.as_ref().map(|_| Vec::new());
+
+        let mut vertices: HashMap<(usize, u32), u32> = HashMap::new();
+        let mut face = Vec::new();
+        for (f, polygon) in self.faces.iter().enumerate() {
+            face.clear();
+            for (k, &vertex) in polygon.iter().enumerate() {
+                let group = find(&mut groups, first_corner[f] + k);
+                let uv = self.uvs.as_ref().map_or(0, |_| self.uv_faces[f][k]);
+
+                let next = mesh.positions.len() as u32;
+                let index = *vertices.entry((group, uv)).or_insert(next);
+                if index == next {
+                    mesh.positions.push(self.positions[vertex as usize]);
+                    let normal = group_normals[group];
+                    normals.push(if normal.magnitude2() > 0.0 {
+                        normal.normalize()
+                    } else {
+                        normal
+                    });
+                    if let (Some(uvs), Some(old)) = (uvs.as_mut(), self.uvs.as_ref()) {
+                        uvs.push(old[uv as usize]);
+                    }
+                }
+                face.push(index);
+            }
+
+            for k in 1..face.len() - 1 {
+                mesh.add_triangle([face[0], face[k], face[k + 1]], self.face_materials[f]);
+            }
+        }
+
+        mesh.normals = Some(normals);
+        mesh.uvs = uvs;
+        mesh
+    }
+}
+
+// Points on a cage of polygons, which are either positions or UVs
+struct Cage {
+    points: Vec<Vector3<f64>>,
+    faces: Vec<Vec<u32>>,
+    creases: HashMap<(u32, u32), f64>,
+}
+
+impl Cage {
+    fn refine(&self, scheme: SubdivisionScheme) -> Cage {
+        match scheme {
+            SubdivisionScheme::CatmullClark => self.catmull_clark(),
+            SubdivisionScheme::Loop => self.loop_subdivide(),
+        }
+    }
+
+    fn triangulate(&self) -> Cage {
+        let faces = self
+            .faces
+            .iter()
+            .flat_map(|face| (1..face.len() - 1).map(move |k| vec![face[0], face[k], face[k + 1]]))
+            .collect();
+
+        Cage {
+            points: self.points.clone(),
+            faces,
+            creases: self.creases.clone(),
+        }
+    }
+
+    // Refer: https://graphics.pixar.com/library/Geri/paper.pdf for the crease rules
+    fn catmull_clark(&self) -> Cage {
+        let topology = Topology::new(self.points.len(), &self.faces, &self.creases);
+        let (vertex_count, edge_count) = (self.points.len(), topology.edges.len());
+
+        let face_points: Vec<Vector3<f64>> = self
+            .faces
+            .iter()
+            .map(|face| {
+                face.iter()
+                    .fold(Vector3::zero(), |sum, &v| sum + self.points[v as usize])
+                    / face.len() as f64
+            })
+            .collect();
+
+        let edge_points = topology.edges.iter().map(|edge| {
+            let middle = self.middle(edge);
+            if edge.sharpness >= 1.0 {
+                return middle;
+            }
+            let faces = face_points[edge.faces[0]] + face_points[edge.faces[1]];
+            let smooth = (2.0 * middle + faces) / 4.0;
+            smooth.lerp(middle, edge.sharpness)
+        });
+
+        let vertex_points = (0..vertex_count).map(|v| {
+            let edges = &topology.vertex_edges[v];
+            let faces = &topology.vertex_faces[v];
+            let n = edges.len() as f64;
+            let q = faces
+                .iter()
+                .fold(Vector3::zero(), |sum, &f| sum + face_points[f])
+                / faces.len() as f64;
+            let r = edges.iter().fold(Vector3::zero(), |sum, &e| {
+                sum + self.middle(&topology.edges[e])
+            }) / n;
+            let smooth = (q + 2.0 * r + (n - 3.0) * self.points[v]) / n;
+            self.vertex_point(&topology, v, smooth)
+        });
+
+        let mut points: Vec<Vector3<f64>> = vertex_points.collect();
+        points.extend(edge_points);
+        points.extend(face_points);
+
+        let edge_point =
+            |a: u32, b: u32| (vertex_count + topology.edge_ids[&edge_key(a, b)]) as u32;
+        let mut faces = Vec::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            let face_point = (vertex_count + edge_count + f) as u32;
+            for k in 0..face.len() {
+                let previous = face[(k + face.len() - 1) % face.len()];
+                let (vertex, next) = (face[k], face[(k + 1) % face.len()]);
+                faces.push(vec![
+                    vertex,
+                    edge_point(vertex, next),
+                    face_point,
+                    edge_point(previous, vertex),
+                ]);
+            }
+        }
+
+        Cage {
+            points,
+            faces,
+            creases: self.child_creases(&topology, |e| (vertex_count + e) as u32),
+        }
+    }
+
+    // Refer: https://www.microsoft.com/en-us/research/publication/smooth-subdivision-surfaces-based-on-triangles/
+    fn loop_subdivide(&self) -> Cage {
+        let topology = Topology::new(self.points.len(), &self.faces, &self.creases);
+        let vertex_count = self.points.len();
+
+        let opposite = |f: usize, edge: &Edge| {
+            let v = self.faces[f]
+                .iter()
+                .find(|v| !edge.ends.contains(v))
+                .unwrap();
+            self.points[*v as usize]
+        };
+        let edge_points = topology.edges.iter().map(|edge| {
+            let middle = self.middle(edge);
+            if edge.sharpness >= 1.0 {
+                return middle;
+            }
+            let smooth = 0.75 * middle
+                + (opposite(edge.faces[0], edge) + opposite(edge.faces[1], edge)) / 8.0;
+            smooth.lerp(middle, edge.sharpness)
+        });
+
+        let vertex_points = (0..vertex_count).map(|v| {
+            let edges = &topology.vertex_edges[v];
+            let n = edges.len() as f64;
+            let neighbours = edges.iter().fold(Vector3::zero(), |sum, &e| {
+                sum + self.points[topology.edges[e].other(v as u32) as usize]
+            });
+            let beta = (0.625 - (0.375 + 0.25 * (2.0 * PI / n).cos()).powi(2)) / n;
+            let smooth = (1.0 - n * beta) * self.points[v] + beta * neighbours;
+            self.vertex_point(&topology, v, smooth)
+        });
+
+        let mut points: Vec<Vector3<f64>> = vertex_points.collect();
+        points.extend(edge_points);
+
+        let edge_point =
+            |a: u32, b: u32| (vertex_count + topology.edge_ids[&edge_key(a, b)]) as u32;
+        let mut faces = Vec::with_capacity(4 * self.faces.len());
+        for face in &self.faces {
+            let (a, b, c) = (face[0], face[1], face[2]);
+            let (ab, bc, ca) = (edge_point(a, b), edge_point(b, c), edge_point(c, a));
+            faces.push(vec![a, ab, ca]);
+            faces.push(vec![ab, b, bc]);
+            faces.push(vec![ca, bc, c]);
+            faces.push(vec![ab, bc, ca]);
+        }
+
+        Cage {
+            points,
+            faces,
+            creases: self.child_creases(&topology, |e| (vertex_count + e) as u32),
+        }
+    }
+
+    fn middle(&self, edge: &Edge) -> Vector3<f64> {
+        (self.points[edge.ends[0] as usize] + self.points[edge.ends[1] as usize]) / 2.0
+    }
+
+    // Moves the smooth position of a vertex onto the crease through it, or keeps the vertex
+    // in place where more than two sharp edges meet and at the corners of open surfaces.
+    // Both schemes share these rules.
+    fn vertex_point(&self, topology: &Topology, v: usize, smooth: Vector3<f64>) -> Vector3<f64> {
+        let point = self.points[v];
+        if topology.vertex_edges[v].is_empty() {
+            return point;
+        }
+
+        let sharp: Vec<&Edge> = topology.vertex_edges[v]
+            .iter()
+            .map(|&e| &topology.edges[e])
+            .filter(|edge| edge.sharpness > 0.0)
+            .collect();
+        let sharp_point = match sharp.len() {
+            0 | 1 => return smooth,
+            2 if topology.vertex_faces[v].len() > 1 => {
+                let a = self.points[sharp[0].other(v as u32) as usize];
+                let b = self.points[sharp[1].other(v as u32) as usize];
+                (a + 6.0 * point + b) / 8.0
+            }
+            _ => point,
+        };
+
+        let sharpness = sharp.iter().map(|edge| edge.sharpness).sum::<f64>() / sharp.len() as f64;
+        if sharpness < 1.0 {
+            smooth.lerp(sharp_point, sharpness)
+        } else {
+            sharp_point
+        }
+    }
+
+    // Both halves of a crease are one level less sharp
+    fn child_creases<F>(&self, topology: &Topology, edge_point: F) -> HashMap<(u32, u32), f64>
+    where
+        F: Fn(usize) -> u32,
+    {
+        let mut creases = HashMap::new();
+        for (&(a, b), &sharpness) in &self.creases {
+            if let Some(&e) = topology.edge_ids.get(&(a, b)) {
+                if sharpness > 1.0 {
+                    creases.insert(edge_key(a, edge_point(e)), sharpness - 1.0);
+                    creases.insert(edge_key(edge_point(e), b), sharpness - 1.0);
+                }
+            }
+        }
+
+        creases
+    }
+}
+
+struct Edge {
+    ends: [u32; 2],
+    faces: Vec<usize>,
+    // Infinite along boundaries and where more than two faces meet
+    sharpness: f64,
+}
+
+impl Edge {
+    fn other(&self, v: u32) -> u32 {
+        if self.ends[0] == v {
+            self.ends[1]
+        } else {
+            self.ends[0]
+        }
+    }
+}
+
+struct Topology {
+    edges: Vec<Edge>,
+    edge_ids: HashMap<(u32, u32), usize>,
+    vertex_edges: Vec<Vec<usize>>,
+    vertex_faces: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(vertices: usize, faces: &[Vec<u32>], creases: &HashMap<(u32, u32), f64>) -> Self {
+        let mut edges: Vec<Edge> = Vec::new();
+        let mut edge_ids = HashMap::new();
+        let mut vertex_edges = vec![Vec::new(); vertices];
+        let mut vertex_faces = vec![Vec::new(); vertices];
+
+        for (f, face) in faces.iter().enumerate() {
+            for (k, &a) in face.iter().enumerate() {
+                let b = face[(k + 1) % face.len()];
+                vertex_faces[a as usize].push(f);
+
+                let key = edge_key(a, b);
+                let e = match edge_ids.get(&key) {
+                    Some(&e) => e,
+                    None => {
+                        let e = edges.len();
+                        edges.push(Edge {
+                            ends: [key.0, key.1],
+                            faces: Vec::new(),
+                            sharpness: creases.get(&key).cloned().unwrap_or(0.0),
+                        });
+                        edge_ids.insert(key, e);
+                        vertex_edges[a as usize].push(e);
+                        vertex_edges[b as usize].push(e);
+                        e
+                    }
+                };
+                edges[e].faces.push(f);
+            }
+        }
+
+        for edge in &mut edges {
+            if edge.faces.len() != 2 {
+                edge.sharpness = f64::INFINITY;
+            }
+        }
+
+        Topology {
+            edges,
+            edge_ids,
+            vertex_edges,
+            vertex_faces,
+        }
+    }
+}
+
+fn edge_key(a: u32, b: u32) -> (u32, u32) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+// Repeats the value of each face for the faces it is split into
+fn expand<F>(faces: &[Vec<u32>], values: &[u32], children: F) -> Vec<u32>
+where
+    F: Fn(&[u32]) -> usize,
+{
+    faces
+        .iter()
+        .zip(values)
+        .flat_map(|(face, &value)| (0..children(face)).map(move |_| value))
+        .collect()
+}
+
+// Finds the group of a corner, halving the path to it on the way
+fn find(groups: &mut [usize], mut i: usize) -> usize {
+    while groups[i] != i {
+        groups[i] = groups[groups[i]];
+        i = groups[i];
+    }
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    fn cage(positions: &[[f64; 3]], faces: &[&[u32]]) -> PolygonMesh {
+        let mut cage = PolygonMesh::new(material());
+        cage.positions = positions
+            .iter()
+            .map(|p| Point3::new(p[0], p[1], p[2]))
+            .collect();
+        for face in faces {
+            cage.add_face(face.to_vec(), 0);
+        }
+        cage
+    }
+
+    fn cube() -> PolygonMesh {
+        let mut positions = Vec::new();
+        for &x in &[-1.0, 1.0] {
+            for &y in &[-1.0, 1.0] {
+                for &z in &[-1.0, 1.0] {
+                    positions.push([x, y, z]);
+                }
+            }
+        }
+        let faces: [&[u32]; 6] = [
+            &[0, 1, 3, 2],
+            &[4, 6, 7, 5],
+            &[0, 4, 5, 1],
+            &[2, 3, 7, 6],
+            &[0, 2, 6, 4],
+            &[1, 5, 7, 3],
+        ];
+        cage(&positions, &faces)
+    }
+
+    #[test]
+    fn refines_a_cube_into_quads() {
+        let refined = cube().subdivide(SubdivisionScheme::CatmullClark, 1);
+        // One point per vertex, edge and face of the cube, and a quad per corner of every face
+        assert_eq!(refined.positions.len(), 8 + 12 + 6);
+        assert_eq!(refined.len(), 24);
+        assert!(refined.faces.iter().all(|face| face.len() == 4));
+        assert_eq!(refined.face_materials.len(), 24);
+
+        // The corners are pulled in along the diagonals
+        let corner = refined.positions[7];
+        assert!((corner - Point3::new(5.0, 5.0, 5.0) / 9.0).magnitude() < 1e-12);
+
+        let refined = cube().subdivide(SubdivisionScheme::CatmullClark, 2);
+        assert_eq!(refined.positions.len(), 26 + 48 + 24);
+        assert_eq!(refined.len(), 96);
+        assert_eq!(refined.to_triangle_mesh().len(), 192);
+    }
+
+    #[test]
+    fn keeps_infinitely_sharp_creases_straight() {
+        // Two slopes of a roof meeting at a ridge along z
+        let mut positions = Vec::new();
+        for &x in &[-1.0, 0.0, 1.0] {
+            for &z in &[-1.0, 0.0, 1.0] {
+                positions.push([x, 1.0 - f64::abs(x), z]);
+            }
+        }
+        let faces: [&[u32]; 4] = [&[0, 1, 4, 3], &[1, 2, 5, 4], &[3, 4, 7, 6], &[4, 5, 8, 7]];
+        let mut roof = cage(&positions, &faces);
+        let smooth = roof.subdivide(SubdivisionScheme::CatmullClark, 1);
+        assert!(smooth.positions[4].y < 1.0);
+
+        roof.set_crease(3, 4, f64::INFINITY);
+        roof.set_crease(5, 4, f64::INFINITY);
+        let refined = roof.subdivide(SubdivisionScheme::CatmullClark, 3);
+        let ridge: Vec<&Point3<f64>> = refined.positions.iter().filter(|p| p.x == 0.0).collect();
+        assert_eq!(ridge.len(), 17);
+        for p in ridge {
+            assert_eq!(p.y, 1.0);
+        }
+        assert_eq!(refined.creases.len(), 16);
+    }
+
+    #[test]
+    fn splits_triangles_in_four() {
+        let tetrahedron = cage(
+            &[
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0],
+                [0.0, 0.0, 1.0],
+            ],
+            &[&[0, 2, 1], &[0, 1, 3], &[0, 3, 2], &[1, 2, 3]],
+        );
+
+        let refined = tetrahedron.subdivide(SubdivisionScheme::Loop, 1);
+        assert_eq!(refined.len(), 16);
+        assert_eq!(refined.positions.len(), 4 + 6);
+        assert!(refined.faces.iter().all(|face| face.len() == 3));
+        let refined = tetrahedron.subdivide(SubdivisionScheme::Loop, 2);
+        assert_eq!(refined.len(), 64);
+        assert_eq!(refined.positions.len(), 10 + 24);
+    }
+}
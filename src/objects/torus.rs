@@ -0,0 +1,159 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::polynomial::solve_quartic;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+
+use std::sync::Arc;
+
+// A ring around the y axis through `center`. The tube of `minor_radius` follows a circle of
+// `major_radius` in the plane y = center.y.
+pub struct Torus {
+    pub center: Point3<f64>,
+    pub major_radius: f64,
+    pub minor_radius: f64,
+    material: Arc<Material>,
+}
+
+impl Torus {
+    pub fn new(
+        center: Point3<f64>,
+        major_radius: f64,
+        minor_radius: f64,
+        material: Arc<Material>,
+    ) -> Self {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+            material,
+        }
+    }
+}
+
+impl Hittable for Torus {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (major, minor) = (self.major_radius, self.minor_radius);
+
+        // The quartic is badly conditioned for distant origins, so it is solved along a unit
+        // direction from the point of the ray closest to the center
+        let length = ray.direction.magnitude();
+        let d = ray.direction / length;
+        let shift = -(ray.origin - self.center).dot(d);
+        let o = ray.origin - self.center + shift * d;
+        if o.magnitude2() > (major + minor).powi(2) {
+            return None;
+        }
+
+        // Substitutes the ray into (|p|² + R² - r²)² = 4R²(x² + z²)
+        let od = o.dot(d);
+        let k = o.magnitude2() + major * major - minor * minor;
+        let four_r2 = 4.0 * major * major;
+        let roots = solve_quartic(
+            1.0,
+            4.0 * od,
+            2.0 * k + 4.0 * od * od - four_r2 * (d.x * d.x + d.z * d.z),
+            4.0 * od * k - 2.0 * four_r2 * (o.x * d.x + o.z * d.z),
+            k * k - four_r2 * (o.x * o.x + o.z * o.z),
+        );
+
+        let t = roots
+            .iter()
+            .map(|s| (s + shift) / length)
+            .find(|&t| t > t_min && t < t_max)?;
+
+        let point = ray.point_at(t);
+        let local = point - self.center.to_vec();
+        let ring = (local.x * local.x + local.z * local.z).sqrt();
+        // The normal points away from the closest point on the center circle
+        let normal = if ring > 0.0 {
+            (local - vec3(local.x, 0.0, local.z) * (major / ring)).normalize()
+        } else {
+            local.normalize()
+        };
+
+        // u goes around the y axis and v around the tube, starting from its outer edge
+        let u = super::get_angle_uv(local.x, local.z);
+        let v = super::get_angle_uv(ring - major, local.y);
+
+        let material = Arc::clone(&self.material);
+        Some(HitRecord::new(ray, t, point, normal, material, u, v))
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        let extent = self.major_radius + self.minor_radius;
+        Some(AABB::new(
+            self.center.to_vec() - vec3(extent, self.minor_radius, extent),
+            self.center.to_vec() + vec3(extent, self.minor_radius, extent),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    fn ring() -> Torus {
+        Torus::new(Point3::new(0.0, 1.0, 0.0), 2.0, 0.5, material())
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn hits_through_the_tube() {
+        let ray = Ray::from(Point3::new(-5.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = ring().hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_close(hit.t, 2.5);
+        assert!((hit.normal - vec3(-1.0, 0.0, 0.0)).magnitude() < 1e-9);
+
+        // Leaving the tube the ray crosses the hole and enters the tube again on the far side
+        let hit = ring().hits(&ray, 2.6, f64::MAX).unwrap();
+        assert_close(hit.t, 3.5);
+        assert!(!hit.front_face);
+        let hit = ring().hits(&ray, 3.6, f64::MAX).unwrap();
+        assert_close(hit.t, 6.5);
+        assert!((hit.normal - vec3(-1.0, 0.0, 0.0)).magnitude() < 1e-9);
+        assert!(hit.front_face);
+    }
+
+    #[test]
+    fn misses_through_the_hole() {
+        let down = Ray::from(Point3::new(0.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0), 0.0);
+        assert!(ring().hits(&down, 0.001, f64::MAX).is_none());
+        let over = Ray::from(Point3::new(-5.0, 1.6, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(ring().hits(&over, 0.001, f64::MAX).is_none());
+
+        let out = Ray::from(Point3::new(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0), 0.0);
+        let hit = ring().hits(&out, 0.001, f64::MAX).unwrap();
+        assert_close(hit.t, 1.5);
+        assert!((hit.normal - vec3(0.0, 0.0, -1.0)).magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn maps_uvs_around_the_ring_and_tube() {
+        // On top of the tube, a quarter of the way around both circles
+        let ray = Ray::from(Point3::new(0.0, 5.0, -2.0), vec3(0.0, -2.0, 0.0), 0.0);
+        let hit = ring().hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_close(hit.t, 1.75);
+        assert!((hit.normal - vec3(0.0, 1.0, 0.0)).magnitude() < 1e-9);
+        assert_close(hit.u, 0.75);
+        assert_close(hit.v, 0.25);
+
+        let ray = Ray::from(Point3::new(-5.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = ring().hits(&ray, 0.001, f64::MAX).unwrap();
+        assert_close(hit.u, 0.5);
+        assert_close(hit.v, 0.0);
+    }
+}
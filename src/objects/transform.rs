@@ -0,0 +1,216 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Deg;
+use cgmath::Matrix4;
+use cgmath::Point3;
+use cgmath::Transform as _;
+use cgmath::Vector3;
+
+use std::f64;
+
+// Places a hittable in the world with an affine transform. Rays are moved into the object's
+// space instead of transforming the object itself.
+pub struct Transform<T: Hittable> {
+    pub hittable: T,
+    matrix: Matrix4<f64>,
+    // The inverse moves rays and its transpose moves normals. A matrix without an inverse
+    // squashes the object flat, which no ray can hit.
+    inverse: Option<(Matrix4<f64>, Matrix4<f64>)>,
+}
+
+impl<T: Hittable> Transform<T> {
+    pub fn new(hittable: T, matrix: Matrix4<f64>) -> Self {
+        Transform {
+            hittable,
+            matrix,
+            inverse: matrix
+                .invert()
+                .map(|inverse| (inverse, inverse.transpose())),
+        }
+    }
+
+    pub fn translate(hittable: T, offset: Vector3<f64>) -> Self {
+        Transform::new(hittable, Matrix4::from_translation(offset))
+    }
+
+    // Rotates counter-clockwise by `angle` degrees about `axis` through the origin
+    pub fn rotate(hittable: T, axis: Vector3<f64>, angle: f64) -> Self {
+        Transform::new(
+            hittable,
+            Matrix4::from_axis_angle(axis.normalize(), Deg(angle)),
+        )
+    }
+
+    pub fn scale(hittable: T, factors: Vector3<f64>) -> Self {
+        Transform::new(
+            hittable,
+            Matrix4::from_nonuniform_scale(factors.x, factors.y, factors.z),
+        )
+    }
+
+    pub fn matrix(&self) -> Matrix4<f64> {
+        self.matrix
+    }
+}
+
+impl<T: Hittable> Hittable for Transform<T> {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let (inverse, normal_matrix) = self.inverse.as_ref()?;
+        let hit = self
+            .hittable
+            .hits(&transform_ray(inverse, ray), t_min, t_max)?;
+        Some(transform_hit(&self.matrix, normal_matrix, hit))
+    }
+
+    fn bounding_box(&self, t0: f64, t1: f64) -> Option<AABB> {
+        let bx = self.hittable.bounding_box(t0, t1)?;
+        Some(transform_box(&self.matrix, &bx))
+    }
+}
+
+// Moves a ray into the space of an object. The direction is left unnormalized so t is the same
+// in both spaces.
+pub(crate) fn transform_ray(inverse: &Matrix4<f64>, ray: &Ray) -> Ray {
+    Ray::from(
+        inverse.transform_point(ray.origin),
+        inverse.transform_vector(ray.direction),
+        ray.time,
+    )
+}
+
+// Moves a hit found in the space of an object back out with `matrix`, where `normal_matrix` is
+// the transpose of its inverse
+pub(crate) fn transform_hit(
+    matrix: &Matrix4<f64>,
+    normal_matrix: &Matrix4<f64>,
+    mut hit: HitRecord,
+) -> HitRecord {
+    hit.p = matrix.transform_point(Point3::from_vec(hit.p)).to_vec();
+    hit.normal = transform_normal(normal_matrix, hit.normal);
+    hit.geometric_normal = transform_normal(normal_matrix, hit.geometric_normal);
+    hit.tangent = hit
+        .tangent
+        .map(|tangent| matrix.transform_vector(tangent).normalize());
+    hit
+}
+
+// Normals transform with the inverse transpose of the matrix applied to points
+pub(crate) fn transform_normal(normal_matrix: &Matrix4<f64>, normal: Vector3<f64>) -> Vector3<f64> {
+    normal_matrix.transform_vector(normal).normalize()
+}
+
+pub(crate) fn transform_box(matrix: &Matrix4<f64>, bx: &AABB) -> AABB {
+    let mut min = vec3(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+    let mut max = vec3(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+    for i in 0..8 {
+        let corner = Point3::new(
+            if i & 1 == 0 { bx.min.x } else { bx.max.x },
+            if i & 2 == 0 { bx.min.y } else { bx.max.y },
+            if i & 4 == 0 { bx.min.z } else { bx.max.z },
+        );
+        let p = matrix.transform_point(corner);
+        for a in 0..3 {
+            min[a] = min[a].min(p[a]);
+            max[a] = max[a].max(p[a]);
+        }
+    }
+
+    AABB::new(min, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+    use crate::materials::Material;
+    use crate::objects::sphere::Sphere;
+
+    use std::sync::Arc;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    // An ellipsoid around (1, 2, 3) reaching 1 along x and y and 2 along z
+    fn ellipsoid() -> Transform<Sphere> {
+        let matrix = Matrix4::from_translation(vec3(1.0, 2.0, 3.0))
+            * Matrix4::from_angle_y(Deg(90.0))
+            * Matrix4::from_nonuniform_scale(2.0, 1.0, 1.0);
+        Transform::new(
+            Sphere::from(Point3::new(0.0, 0.0, 0.0), 1.0, material()),
+            matrix,
+        )
+    }
+
+    #[test]
+    fn hits_at_the_world_distance() {
+        let ellipsoid = ellipsoid();
+
+        let ray = Ray::from(Point3::new(1.0, 2.0, 10.0), vec3(0.0, 0.0, -1.0), 0.0);
+        let hit = ellipsoid.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 5.0).abs() < 1e-9);
+        assert!((hit.p - vec3(1.0, 2.0, 5.0)).magnitude() < 1e-9);
+        assert!((hit.normal - vec3(0.0, 0.0, 1.0)).magnitude() < 1e-9);
+
+        let ray = Ray::from(Point3::new(-5.0, 2.0, 3.0), vec3(2.0, 0.0, 0.0), 0.0);
+        let hit = ellipsoid.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 2.5).abs() < 1e-9);
+
+        let beside = Ray::from(Point3::new(-5.0, 2.0, 5.1), vec3(1.0, 0.0, 0.0), 0.0);
+        assert!(ellipsoid.hits(&beside, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn moves_normals_with_the_inverse_transpose() {
+        let ellipsoid = ellipsoid();
+
+        // Hits where x² + y² + z²/4 = 1 around the center, at (-0.6, 0, 1.6)
+        let ray = Ray::from(Point3::new(-5.0, 2.0, 4.6), vec3(1.0, 0.0, 0.0), 0.0);
+        let hit = ellipsoid.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 5.4).abs() < 1e-9);
+
+        let inverse = ellipsoid.matrix().invert().unwrap();
+        let local = inverse.transform_point(Point3::from_vec(hit.p)).to_vec();
+        let expected = inverse.transpose().transform_vector(local).normalize();
+        assert!((hit.normal - expected).magnitude() < 1e-9);
+        // The gradient of the ellipsoid, which the transformed normal does not match
+        assert!((hit.normal - vec3(-1.2, 0.0, 0.8).normalize()).magnitude() < 1e-9);
+        assert!(
+            (ellipsoid.matrix().transform_vector(local).normalize() - hit.normal).magnitude() > 0.1
+        );
+    }
+
+    #[test]
+    fn encloses_the_transformed_object() {
+        let matrix = Matrix4::from_translation(vec3(-1.0, 0.5, 2.0))
+            * Matrix4::from_axis_angle(vec3(1.0, 1.0, 0.0).normalize(), Deg(30.0))
+            * Matrix4::from_nonuniform_scale(3.0, 0.5, 1.0);
+        let transform = Transform::new(
+            Sphere::from(Point3::new(0.0, 1.0, 0.0), 1.0, material()),
+            matrix,
+        );
+        let bx = transform.bounding_box(0.0, 1.0).unwrap();
+
+        for i in 0..50 {
+            for j in 0..=25 {
+                let phi = f64::from(i) * 2.0 * f64::consts::PI / 50.0;
+                let theta = f64::from(j) * f64::consts::PI / 25.0;
+                let p = Point3::new(
+                    theta.sin() * phi.cos(),
+                    1.0 + theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let p = matrix.transform_point(p);
+                for a in 0..3 {
+                    assert!(bx.min[a] <= p[a] + 1e-12 && p[a] <= bx.max[a] + 1e-12);
+                }
+            }
+        }
+    }
+}
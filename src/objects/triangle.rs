@@ -0,0 +1,345 @@
+use super::HitRecord;
+use super::Hittable;
+use crate::aabb::AABB;
+use crate::materials::Material;
+use crate::ray::Ray;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Point3;
+use cgmath::Vector3;
+
+use std::sync::Arc;
+
+pub struct Triangle {
+    pub vertices: [Point3<f64>; 3],
+    pub normals: Option<[Vector3<f64>; 3]>,
+    pub uvs: [(f64, f64); 3],
+    material: Arc<Material>,
+}
+
+impl Triangle {
+    pub fn new(
+        vertices: [Point3<f64>; 3],
+        normals: Option<[Vector3<f64>; 3]>,
+        uvs: [(f64, f64); 3],
+        material: Arc<Material>,
+    ) -> Triangle {
+        Triangle {
+            vertices,
+            normals,
+            uvs,
+            material,
+        }
+    }
+
+    pub fn from(
+        v0: Point3<f64>,
+        v1: Point3<f64>,
+        v2: Point3<f64>,
+        material: Arc<Material>,
+    ) -> Triangle {
+        Triangle::new(
+            [v0, v1, v2],
+            None,
+            [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0)],
+            material,
+        )
+    }
+}
+
+impl Hittable for Triangle {
+    fn hits(&self, ray: &Ray, t_min: f64, t_max: f64) -> Option<HitRecord> {
+        let [p0, p1, p2] = self.vertices;
+        let (t, b0, b1, b2) = intersect(p0, p1, p2, ray, t_min, t_max)?;
+
+        let point = b0 * p0.to_vec() + b1 * p1.to_vec() + b2 * p2.to_vec();
+        let normal = self
+            .normals
+            .map(|[n0, n1, n2]| (b0 * n0 + b1 * n1 + b2 * n2).normalize());
+        let mut geometric = (p1 - p0).cross(p2 - p0).normalize();
+        // Vertex normals tell which side is out, whichever way the triangle winds
+        if matches!(normal, Some(normal) if geometric.dot(normal) < 0.0) {
+            geometric = -geometric;
+        }
+        let u = b0 * self.uvs[0].0 + b1 * self.uvs[1].0 + b2 * self.uvs[2].0;
+        let v = b0 * self.uvs[0].1 + b1 * self.uvs[1].1 + b2 * self.uvs[2].1;
+        let material = Arc::clone(&self.material);
+
+        let mut rec = HitRecord::new(ray, t, point, geometric, material, u, v);
+        if let Some(normal) = normal {
+            rec.set_shading_normal(normal);
+        }
+        Some(rec)
+    }
+
+    fn bounding_box(&self, _t0: f64, _t1: f64) -> Option<AABB> {
+        Some(triangle_box(
+            self.vertices[0],
+            self.vertices[1],
+            self.vertices[2],
+        ))
+    }
+}
+
+pub(crate) fn triangle_box(p0: Point3<f64>, p1: Point3<f64>, p2: Point3<f64>) -> AABB {
+    let min = vec3(
+        p0.x.min(p1.x).min(p2.x),
+        p0.y.min(p1.y).min(p2.y),
+        p0.z.min(p1.z).min(p2.z),
+    );
+    let max = vec3(
+        p0.x.max(p1.x).max(p2.x),
+        p0.y.max(p1.y).max(p2.y),
+        p0.z.max(p1.z).max(p2.z),
+    );
+
+    AABB::new(min, max).pad(1e-4)
+}
+
+// Watertight ray/triangle intersection, returns the ray parameter and the barycentric
+// coordinates of the hit.
+// Refer: Woop, Benthin and Wald, "Watertight Ray/Triangle Intersection" (JCGT 2013)
+pub(crate) fn intersect(
+    p0: Point3<f64>,
+    p1: Point3<f64>,
+    p2: Point3<f64>,
+    ray: &Ray,
+    t_min: f64,
+    t_max: f64,
+) -> Option<(f64, f64, f64, f64)> {
+    let d = ray.direction;
+    let kz = max_dimension(vec3(d.x.abs(), d.y.abs(), d.z.abs()));
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+
+    let d = vec3(d[kx], d[ky], d[kz]);
+    let permute = |p: Point3<f64>| {
+        let p = p - ray.origin;
+        vec3(p[kx], p[ky], p[kz])
+    };
+    let mut p0t = permute(p0);
+    let mut p1t = permute(p1);
+    let mut p2t = permute(p2);
+
+    let sx = -d.x / d.z;
+    let sy = -d.y / d.z;
+    let sz = 1.0 / d.z;
+    p0t.x += sx * p0t.z;
+    p0t.y += sy * p0t.z;
+    p1t.x += sx * p1t.z;
+    p1t.y += sy * p1t.z;
+    p2t.x += sx * p2t.z;
+    p2t.y += sy * p2t.z;
+
+    let e0 = p1t.x * p2t.y - p1t.y * p2t.x;
+    let e1 = p2t.x * p0t.y - p2t.y * p0t.x;
+    let e2 = p0t.x * p1t.y - p0t.y * p1t.x;
+
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
+
+    // A ray exactly through an edge shared by two triangles only counts for one of them, the
+    // one that runs along the edge in the direction picked here
+    let owns_edge = |e: f64, a: Vector3<f64>, b: Vector3<f64>| {
+        let (dx, dy) = ((b.x - a.x) * det, (b.y - a.y) * det);
+        e != 0.0 || dy > 0.0 || (dy == 0.0 && dx > 0.0)
+    };
+    if !(owns_edge(e0, p1t, p2t) && owns_edge(e1, p2t, p0t) && owns_edge(e2, p0t, p1t)) {
+        return None;
+    }
+
+    p0t.z *= sz;
+    p1t.z *= sz;
+    p2t.z *= sz;
+    let t_scaled = e0 * p0t.z + e1 * p1t.z + e2 * p2t.z;
+    if det < 0.0 && (t_scaled >= t_min * det || t_scaled <= t_max * det) {
+        return None;
+    }
+    if det > 0.0 && (t_scaled <= t_min * det || t_scaled >= t_max * det) {
+        return None;
+    }
+
+    let inv_det = 1.0 / det;
+    Some((t_scaled * inv_det, e0 * inv_det, e1 * inv_det, e2 * inv_det))
+}
+
+fn max_dimension(v: Vector3<f64>) -> usize {
+    if v.x > v.y {
+        if v.x > v.z {
+            0
+        } else {
+            2
+        }
+    } else if v.y > v.z {
+        1
+    } else {
+        2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::materials::lambertian::Lambertian;
+
+    fn material() -> Arc<Material> {
+        Arc::new(Material::Lambertian(Lambertian::color(0.5, 0.5, 0.5)))
+    }
+
+    fn down(x: f64, y: f64, direction: Vector3<f64>) -> Ray {
+        Ray::from(Point3::new(x, y, 0.0) - 3.0 * direction, direction, 0.0)
+    }
+
+    #[test]
+    fn interpolates_at_the_hit() {
+        let normals = [
+            vec3(0.0, 0.0, -1.0),
+            vec3(1.0, 0.0, -1.0).normalize(),
+            vec3(0.0, 1.0, -1.0).normalize(),
+        ];
+        let triangle = Triangle::new(
+            [
+                Point3::new(0.0, 0.0, 0.0),
+                Point3::new(2.0, 0.0, 0.0),
+                Point3::new(0.0, 2.0, 0.0),
+            ],
+            Some(normals),
+            [(0.5, 0.5), (1.0, 0.5), (0.5, 1.0)],
+            material(),
+        );
+
+        let ray = Ray::from(Point3::new(0.4, 0.6, -3.0), vec3(0.0, 0.0, 2.0), 0.0);
+        let hit = triangle.hits(&ray, 0.001, f64::MAX).unwrap();
+        assert!((hit.t - 1.5).abs() < 1e-12);
+        assert!((hit.p - vec3(0.4, 0.6, 0.0)).magnitude() < 1e-12);
+        assert!((hit.u - 0.6).abs() < 1e-12 && (hit.v - 0.65).abs() < 1e-12);
+        let normal = (0.5 * normals[0] + 0.2 * normals[1] + 0.3 * normals[2]).normalize();
+        assert!((hit.normal - normal).magnitude() < 1e-12);
+
+        assert!(triangle.hits(&ray, 0.001, 1.49).is_none());
+        assert!(triangle.hits(&ray, 1.51, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn hits_one_of_two_triangles_through_their_shared_edge() {
+        let (a, b, c, d) = (
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.0, 1.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        );
+        // Wound the same way and against each other, the shared edge runs from a to c
+        let pairs = [
+            [(a, b, c), (a, c, d)],
+            [(a, b, c), (c, a, d)],
+            [(b, a, c), (a, c, d)],
+        ];
+        let directions = [
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.3, -0.2, 1.0),
+            vec3(1.0, 1.0, 1.0),
+            vec3(-0.7, 0.1, 0.4),
+        ];
+        for pair in &pairs {
+            for &direction in &directions {
+                for i in 1..32 {
+                    let s = f64::from(i) / 32.0 + if i % 2 == 0 { 0.0 } else { 0.1 / 32.0 };
+                    let ray = down(s, s, direction);
+                    let hits = pair
+                        .iter()
+                        .filter(|&&(p0, p1, p2)| {
+                            intersect(p0, p1, p2, &ray, 0.001, f64::MAX).is_some()
+                        })
+                        .count();
+                    assert_eq!(hits, 1, "{:?} at {}", direction, s);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn misses_outside_the_edges() {
+        let triangle = Triangle::from(
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+            material(),
+        );
+        let direction = vec3(0.0, 0.0, 1.0);
+
+        assert!(triangle
+            .hits(&down(0.5, -1e-9, direction), 0.001, f64::MAX)
+            .is_none());
+        assert!(triangle
+            .hits(&down(-1e-9, 0.5, direction), 0.001, f64::MAX)
+            .is_none());
+        assert!(triangle
+            .hits(&down(0.5, 0.5 + 1e-9, direction), 0.001, f64::MAX)
+            .is_none());
+        assert!(triangle
+            .hits(&down(0.5, 1e-9, direction), 0.001, f64::MAX)
+            .is_some());
+        assert!(triangle
+            .hits(&down(0.5, 0.5 - 1e-9, direction), 0.001, f64::MAX)
+            .is_some());
+    }
+
+    #[test]
+    fn misses_degenerate_triangles() {
+        let ray = down(1.0, 1.0, vec3(0.0, 0.0, 1.0));
+        let p = Point3::new(1.0, 1.0, 0.0);
+        let line = (Point3::new(0.0, 0.0, 0.0), p, Point3::new(2.0, 2.0, 0.0));
+        assert!(intersect(line.0, line.1, line.2, &ray, 0.001, f64::MAX).is_none());
+        assert!(intersect(p, p, p, &ray, 0.001, f64::MAX).is_none());
+
+        // Edge on to the ray
+        let edge_on = (
+            Point3::new(1.0, 1.0, -1.0),
+            Point3::new(1.0, 1.0, 1.0),
+            Point3::new(1.0, 2.0, 0.0),
+        );
+        assert!(intersect(edge_on.0, edge_on.1, edge_on.2, &ray, 0.001, f64::MAX).is_none());
+    }
+
+    #[test]
+    fn faces_the_ray_from_either_side() {
+        let vertices = [
+            Point3::new(0.0, 0.0, 0.0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(0.0, 1.0, 0.0),
+        ];
+        let flat = Triangle::new(vertices, None, [(0.0, 0.0); 3], material());
+        let tilted = vec3(0.1, 0.0, 1.0).normalize();
+        // Vertex normals against the winding decide which side is out
+        let smooth = Triangle::new(
+            [vertices[0], vertices[2], vertices[1]],
+            Some([tilted; 3]),
+            [(0.0, 0.0); 3],
+            material(),
+        );
+
+        for triangle in &[flat, smooth] {
+            let hit = triangle
+                .hits(&down(0.2, 0.2, vec3(0.0, 0.0, -1.0)), 0.001, f64::MAX)
+                .unwrap();
+            assert!(hit.front_face);
+            assert_eq!(hit.geometric_normal, vec3(0.0, 0.0, 1.0));
+            assert!(hit.normal.z > 0.0);
+
+            let hit = triangle
+                .hits(&down(0.2, 0.2, vec3(0.0, 0.0, 1.0)), 0.001, f64::MAX)
+                .unwrap();
+            assert!(!hit.front_face);
+            assert_eq!(hit.geometric_normal, vec3(0.0, 0.0, -1.0));
+            assert!(hit.normal.z < 0.0);
+        }
+    }
+}
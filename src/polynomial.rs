@@ -0,0 +1,242 @@
+use std::f64;
+use std::iter::once;
+use std::ops::Deref;
+
+// The real roots of a polynomial of at most fourth degree, in ascending order
+#[derive(Debug, Clone, Copy)]
+pub struct Roots {
+    values: [f64; 4],
+    len: usize,
+}
+
+impl Roots {
+    fn new() -> Self {
+        Roots {
+            values: [0.0; 4],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, root: f64) {
+        self.values[self.len] = root;
+        self.len += 1;
+    }
+}
+
+impl Deref for Roots {
+    type Target = [f64];
+
+    fn deref(&self) -> &[f64] {
+        &self.values[..self.len]
+    }
+}
+
+// Solves a·x + b = 0
+pub fn solve_linear(a: f64, b: f64) -> Roots {
+    let mut roots = Roots::new();
+    if a != 0.0 {
+        roots.push(-b / a);
+    }
+
+    roots
+}
+
+// Solves a·x² + b·x + c = 0 without the cancellation of the textbook formula
+pub fn solve_quadratic(a: f64, b: f64, c: f64) -> Roots {
+    if a == 0.0 {
+        return solve_linear(b, c);
+    }
+
+    let mut roots = Roots::new();
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 {
+        return roots;
+    }
+
+    if discriminant == 0.0 {
+        roots.push(-b / (2.0 * a));
+        return roots;
+    }
+
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    let (x0, x1) = (q / a, c / q);
+    roots.push(x0.min(x1));
+    roots.push(x0.max(x1));
+    roots
+}
+
+// Solves a·x³ + b·x² + c·x + d = 0
+pub fn solve_cubic(a: f64, b: f64, c: f64, d: f64) -> Roots {
+    if negligible(a, &[b, c, d]) {
+        return solve_quadratic(b, c, d);
+    }
+
+    isolate(&[a, b, c, d], &solve_quadratic(3.0 * a, 2.0 * b, c))
+}
+
+// Solves a·x⁴ + b·x³ + c·x² + d·x + e = 0
+pub fn solve_quartic(a: f64, b: f64, c: f64, d: f64, e: f64) -> Roots {
+    if negligible(a, &[b, c, d, e]) {
+        return solve_cubic(b, c, d, e);
+    }
+
+    isolate(&[a, b, c, d, e], &solve_cubic(4.0 * a, 3.0 * b, 2.0 * c, d))
+}
+
+// The closed form solutions of cubics and quartics lose most of their precision in the
+// cases ray tracing runs into all the time, like nearly tangent rays. Instead, the roots of
+// the derivative split the line into pieces where the polynomial is monotonic, and each piece
+// holds at most one root which is found by safeguarded Newton iteration. A root where the
+// polynomial touches zero without crossing it sits on a critical point, so a critical point
+// where the value is within rounding error of zero is taken as a root.
+fn isolate(coefficients: &[f64], critical: &[f64]) -> Roots {
+    // Every root lies strictly inside the Cauchy bound
+    let bound = 1.0
+        + coefficients[1..]
+            .iter()
+            .map(|c| (c / coefficients[0]).abs())
+            .fold(0.0, f64::max);
+
+    let mut roots = Roots::new();
+    let mut lo = -bound;
+    let mut f_lo = evaluate(coefficients, lo).0;
+    let mut zero_lo = false;
+    for &hi in critical.iter().chain(once(&bound)) {
+        if hi <= lo {
+            continue;
+        }
+
+        let f_hi = evaluate(coefficients, hi).0;
+        let zero_hi = f_hi.abs() <= rounding_error(coefficients, hi);
+        if zero_hi {
+            roots.push(hi);
+        } else if !zero_lo && (f_lo < 0.0) != (f_hi < 0.0) {
+            roots.push(refine(coefficients, lo, hi, f_lo));
+        }
+
+        lo = hi;
+        f_lo = f_hi;
+        zero_lo = zero_hi;
+    }
+
+    roots
+}
+
+// Finds the root in (lo, hi), which p brackets with a change of sign
+fn refine(coefficients: &[f64], mut lo: f64, mut hi: f64, f_lo: f64) -> f64 {
+    let mut x = 0.5 * (lo + hi);
+    for _ in 0..100 {
+        let (f, df) = evaluate(coefficients, x);
+        if f == 0.0 {
+            return x;
+        }
+
+        if (f < 0.0) == (f_lo < 0.0) {
+            lo = x;
+        } else {
+            hi = x;
+        }
+
+        // Newton steps that leave the bracket fall back to bisection
+        let newton = x - f / df;
+        let next = if newton > lo && newton < hi {
+            newton
+        } else {
+            0.5 * (lo + hi)
+        };
+
+        if (next - x).abs() <= f64::EPSILON * x.abs().max(1.0) {
+            return next;
+        }
+        x = next;
+    }
+
+    x
+}
+
+// A leading coefficient that is negligible next to the others only adds roots too far away to
+// matter, and would push the Cauchy bound out to where evaluating overflows
+fn negligible(leading: f64, rest: &[f64]) -> bool {
+    leading.abs() <= f64::EPSILON * rest.iter().fold(0.0, |max: f64, c| max.max(c.abs()))
+}
+
+// Bounds the error of evaluating the polynomial at x with Horner's method, with some slack for
+// critical points that are themselves rounded
+// Refer: Higham, Accuracy and Stability of Numerical Algorithms, section 5.1
+fn rounding_error(coefficients: &[f64], x: f64) -> f64 {
+    let magnitude = coefficients
+        .iter()
+        .fold(0.0, |sum: f64, c| sum * x.abs() + c.abs());
+    8.0 * coefficients.len() as f64 * f64::EPSILON * magnitude
+}
+
+// Evaluates the polynomial and its derivative at x with Horner's method
+fn evaluate(coefficients: &[f64], x: f64) -> (f64, f64) {
+    let mut value = 0.0;
+    let mut derivative = 0.0;
+    for &c in coefficients {
+        derivative = derivative * x + value;
+        value = value * x + c;
+    }
+
+    (value, derivative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(roots: &[f64], expected: &[f64]) {
+        assert_eq!(roots.len(), expected.len(), "{:?} != {:?}", roots, expected);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!(
+                (root - expected).abs() < 1e-6,
+                "{:?} != {:?}",
+                roots,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn solves_four_distinct_roots() {
+        // (x + 2)(x - 1)(x - 3)(x - 4)
+        assert_roots(
+            &solve_quartic(1.0, -6.0, 3.0, 26.0, -24.0),
+            &[-2.0, 1.0, 3.0, 4.0],
+        );
+    }
+
+    #[test]
+    fn solves_double_roots() {
+        // (x - 1)²(x - 2)(x - 3)
+        assert_roots(
+            &solve_quartic(1.0, -7.0, 17.0, -17.0, 6.0),
+            &[1.0, 2.0, 3.0],
+        );
+        // (x² - 2)², whose roots and critical points are both irrational
+        let root = 2f64.sqrt();
+        assert_roots(&solve_quartic(1.0, 0.0, -4.0, 0.0, 4.0), &[-root, root]);
+        // (x - 0.5)²(x + 1)
+        assert_roots(&solve_cubic(1.0, 0.0, -0.75, 0.25), &[-1.0, 0.5]);
+    }
+
+    #[test]
+    fn solves_no_real_roots() {
+        // (x² + 1)(x² + 4)
+        assert_roots(&solve_quartic(1.0, 0.0, 5.0, 0.0, 4.0), &[]);
+        // (x² - 2x + 2)², which only touches its minimum of 1
+        assert_roots(&solve_quartic(1.0, -4.0, 8.0, -8.0, 5.0), &[]);
+    }
+
+    #[test]
+    fn lowers_the_degree_of_a_negligible_leading_coefficient() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(
+            &solve_quartic(1e-300, 1.0, -6.0, 11.0, -6.0),
+            &[1.0, 2.0, 3.0],
+        );
+        assert_roots(&solve_quartic(0.0, 1.0, -6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+        assert_roots(&solve_cubic(1e-300, 1.0, -3.0, 2.0), &[1.0, 2.0]);
+    }
+}
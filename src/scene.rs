@@ -14,32 +14,33 @@ use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use std::f64;
 
-pub struct Scene<'a> {
+pub struct Scene {
     pub camera: Camera,
     pub width: u16,
     pub height: u16,
     pub samples: u64,
-    pub world: BvhTree<'a>,
+    pub world: BvhTree,
     pub time0: f64,
     pub time1: f64,
 }
 
-impl<'a> Scene<'a> {
+impl Scene {
+    // Takes the world by value, since the BVH owns the objects it is built over
     pub fn new(
         camera: Camera,
         width: u16,
         height: u16,
         samples: u64,
-        world: &'a mut HittableList,
+        world: HittableList,
         time0: f64,
         time1: f64,
-    ) -> Scene<'a> {
+    ) -> Scene {
         Scene {
             camera,
             width,
             height,
             samples,
-            world: BvhTree::new(&mut world.objects[..], time0, time1),
+            world: BvhTree::new(world.objects, time0, time1),
             time0,
             time1,
         }
@@ -82,7 +83,7 @@ impl<'a> Scene<'a> {
     }
 }
 
-fn lerp(ray: &Ray, world: &Hittable, depth: i32) -> Vector3<f64> {
+fn lerp(ray: &Ray, world: &dyn Hittable, depth: i32) -> Vector3<f64> {
     if let Some(hit) = world.hits(ray, 0.001, f64::MAX) {
         let emitted = hit.material.emitted(hit.u, hit.v, hit.p);
         if depth < 50 {
@@ -26,4 +26,8 @@ impl Textured for CheckedTexture {
             self.even.value(u, v, p)
         }
     }
+
+    fn max_value(&self) -> f64 {
+        self.odd.max_value().max(self.even.max_value())
+    }
 }
@@ -23,4 +23,8 @@ impl Textured for ConstantTexture {
     fn value(&self, _u: f64, _v: f64, _p: Vector3<f64>) -> Vector3<f64> {
         self.color
     }
+
+    fn max_value(&self) -> f64 {
+        self.color.x.max(self.color.y).max(self.color.z)
+    }
 }
@@ -0,0 +1,92 @@
+use super::Textured;
+
+use cgmath::prelude::*;
+use cgmath::vec3;
+use cgmath::Vector3;
+use image::ImageResult;
+use image::RgbImage;
+
+use std::path::Path;
+use std::sync::Arc;
+
+pub struct ImageTexture {
+    image: Arc<RgbImage>,
+    // Color images are stored sRGB encoded and are decoded to linear values. Other data, such as
+    // heights or roughness, is read as it is stored.
+    srgb: bool,
+    // Scales every value, as the color factors of glTF materials do
+    tint: Vector3<f64>,
+}
+
+impl ImageTexture {
+    // An sRGB encoded color image
+    pub fn new(image: Arc<RgbImage>) -> Self {
+        ImageTexture::tinted(image, vec3(1.0, 1.0, 1.0))
+    }
+
+    // An sRGB encoded color image multiplied by `tint`
+    pub fn tinted(image: Arc<RgbImage>, tint: Vector3<f64>) -> Self {
+        ImageTexture {
+            image,
+            srgb: true,
+            tint,
+        }
+    }
+
+    // An image of linear values
+    pub fn linear(image: Arc<RgbImage>) -> Self {
+        ImageTexture {
+            image,
+            srgb: false,
+            tint: vec3(1.0, 1.0, 1.0),
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let image = image::open(path)?.to_rgb();
+        Ok(ImageTexture::new(Arc::new(image)))
+    }
+
+    pub fn open_linear<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let image = image::open(path)?.to_rgb();
+        Ok(ImageTexture::linear(Arc::new(image)))
+    }
+}
+
+impl Textured for ImageTexture {
+    fn value(&self, u: f64, v: f64, _p: Vector3<f64>) -> Vector3<f64> {
+        let (width, height) = self.image.dimensions();
+        if width == 0 || height == 0 {
+            return vec3(0.0, 1.0, 1.0);
+        }
+
+        let u = u - u.floor();
+        let v = v - v.floor();
+        let i = ((u * f64::from(width)) as u32).min(width - 1);
+        let j = (((1.0 - v) * f64::from(height)) as u32).min(height - 1);
+        let pixel = self.image.get_pixel(i, j);
+        let channel = |c: u8| {
+            let c = f64::from(c) / 255.0;
+            if self.srgb {
+                srgb_to_linear(c)
+            } else {
+                c
+            }
+        };
+
+        vec3(channel(pixel[0]), channel(pixel[1]), channel(pixel[2])).mul_element_wise(self.tint)
+    }
+
+    fn max_value(&self) -> f64 {
+        self.tint.x.max(self.tint.y).max(self.tint.z)
+    }
+}
+
+// Refer: https://en.wikipedia.org/wiki/SRGB#Transformation
+fn srgb_to_linear(c: f64) -> f64 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
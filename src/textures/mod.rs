@@ -2,12 +2,19 @@ use cgmath::Vector3;
 
 pub trait Textured {
     fn value(&self, u: f64, v: f64, p: Vector3<f64>) -> Vector3<f64>;
+
+    // Upper bound of every channel returned by `value`. Most textures are colors within [0, 1].
+    fn max_value(&self) -> f64 {
+        1.0
+    }
 }
 
 pub enum Texture {
     ConstantTexture(constant_texture::ConstantTexture),
     CheckedTexture(checked_texture::CheckedTexture),
     NoiseTexture(noise_texture::NoiseTexture),
+    ImageTexture(image_texture::ImageTexture),
+    VoxelGrid(voxel_grid::VoxelGrid),
 }
 
 impl Textured for Texture {
@@ -16,10 +23,24 @@ impl Textured for Texture {
             Texture::ConstantTexture(ref tex) => tex.value(u, v, p),
             Texture::CheckedTexture(ref tex) => tex.value(u, v, p),
             Texture::NoiseTexture(ref tex) => tex.value(u, v, p),
+            Texture::ImageTexture(ref tex) => tex.value(u, v, p),
+            Texture::VoxelGrid(ref tex) => tex.value(u, v, p),
+        }
+    }
+
+    fn max_value(&self) -> f64 {
+        match *self {
+            Texture::ConstantTexture(ref tex) => tex.max_value(),
+            Texture::CheckedTexture(ref tex) => tex.max_value(),
+            Texture::NoiseTexture(ref tex) => tex.max_value(),
+            Texture::ImageTexture(ref tex) => tex.max_value(),
+            Texture::VoxelGrid(ref tex) => tex.max_value(),
         }
     }
 }
 
 pub mod checked_texture;
 pub mod constant_texture;
+pub mod image_texture;
 pub mod noise_texture;
+pub mod voxel_grid;
@@ -13,6 +13,12 @@ pub struct Perlin {
     pub perm_z: Vec<usize>,
 }
 
+impl Default for Perlin {
+    fn default() -> Self {
+        Perlin::new()
+    }
+}
+
 impl Perlin {
     pub fn new() -> Self {
         Perlin {
@@ -58,6 +64,7 @@ impl Perlin {
         p
     }
 
+    #[allow(clippy::needless_range_loop)]
     pub fn noise(&self, p: Vector3<f64>) -> f64 {
         let u = p.x - p.x.floor();
         let v = p.y - p.y.floor();
@@ -128,6 +135,7 @@ impl Textured for NoiseTexture {
     }
 }
 
+#[allow(clippy::needless_range_loop)]
 fn perlin_interp(c: &[[[Vector3<f64>; 2]; 2]], u: f64, v: f64, w: f64) -> f64 {
     let mut accum = 0.0;
     let uu = u * u * (3.0 - 2.0 * u);
@@ -0,0 +1,155 @@
+use super::Textured;
+
+use cgmath::vec3;
+use cgmath::Vector3;
+
+use std::fs::File;
+use std::io;
+use std::io::BufReader;
+use std::io::Read;
+use std::path::Path;
+
+// A scalar field sampled on a regular grid spanning the box from `min` to `max`, such as a
+// density volume exported from a simulation. Values are trilinearly interpolated and zero
+// outside the grid.
+pub struct VoxelGrid {
+    pub dims: [usize; 3],
+    pub min: Vector3<f64>,
+    pub max: Vector3<f64>,
+    data: Vec<f32>,
+    max_value: f64,
+}
+
+impl VoxelGrid {
+    pub fn new(dims: [usize; 3], data: Vec<f32>, min: Vector3<f64>, max: Vector3<f64>) -> Self {
+        assert_eq!(
+            data.len(),
+            dims[0] * dims[1] * dims[2],
+            "Voxel data does not match the grid dimensions"
+        );
+        let max_value = data.iter().cloned().fold(0.0, f32::max);
+
+        VoxelGrid {
+            dims,
+            min,
+            max,
+            data,
+            max_value: f64::from(max_value),
+        }
+    }
+
+    // Reads little endian f32 voxels with x varying fastest, then y, then z
+    pub fn open_raw<P: AsRef<Path>>(
+        path: P,
+        dims: [usize; 3],
+        min: Vector3<f64>,
+        max: Vector3<f64>,
+    ) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut data = Vec::with_capacity(dims[0] * dims[1] * dims[2]);
+        let mut bytes = [0u8; 4];
+        for _ in 0..dims[0] * dims[1] * dims[2] {
+            reader.read_exact(&mut bytes)?;
+            data.push(f32::from_le_bytes(bytes));
+        }
+
+        Ok(VoxelGrid::new(dims, data, min, max))
+    }
+
+    pub fn sample(&self, p: Vector3<f64>) -> f64 {
+        let mut cell = [0usize; 3];
+        let mut frac = [0.0; 3];
+        for a in 0..3 {
+            if self.dims[a] == 0 || p[a] < self.min[a] || p[a] > self.max[a] {
+                return 0.0;
+            }
+            // Voxel centers sit at the middle of each of the `dims` cells
+            let x = (p[a] - self.min[a]) / (self.max[a] - self.min[a]) * self.dims[a] as f64 - 0.5;
+            let x = x.max(0.0).min((self.dims[a] - 1) as f64);
+            cell[a] = (x.floor() as usize).min(self.dims[a].saturating_sub(2));
+            frac[a] = x - cell[a] as f64;
+        }
+
+        let mut accum = 0.0;
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0usize; 3];
+            for a in 0..3 {
+                let offset = (corner >> a) & 1;
+                index[a] = (cell[a] + offset).min(self.dims[a] - 1);
+                weight *= if offset == 1 { frac[a] } else { 1.0 - frac[a] };
+            }
+            accum += weight * f64::from(self.voxel(index));
+        }
+
+        accum
+    }
+
+    fn voxel(&self, [x, y, z]: [usize; 3]) -> f32 {
+        self.data[(z * self.dims[1] + y) * self.dims[0] + x]
+    }
+}
+
+impl Textured for VoxelGrid {
+    fn value(&self, _u: f64, _v: f64, p: Vector3<f64>) -> Vector3<f64> {
+        let value = self.sample(p);
+        vec3(value, value, value)
+    }
+
+    fn max_value(&self) -> f64 {
+        self.max_value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Voxel (x, y, z) holds x + 10y + 100z, with centers at (x + 0.5, y + 0.5, 2z + 1)
+    fn grid() -> VoxelGrid {
+        let mut data = Vec::new();
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..4 {
+                    data.push((x + 10 * y + 100 * z) as f32);
+                }
+            }
+        }
+        VoxelGrid::new([4, 2, 2], data, vec3(0.0, 0.0, 0.0), vec3(4.0, 2.0, 4.0))
+    }
+
+    #[test]
+    fn samples_voxel_centers_exactly() {
+        let grid = grid();
+        for z in 0..2 {
+            for y in 0..2 {
+                for x in 0..4 {
+                    let center = vec3(x as f64 + 0.5, y as f64 + 0.5, 2.0 * z as f64 + 1.0);
+                    assert_eq!(grid.sample(center), (x + 10 * y + 100 * z) as f64);
+                }
+            }
+        }
+
+        // Halfway between centers, and clamped to the outer centers up to the sides
+        assert_eq!(grid.sample(vec3(2.0, 0.5, 1.0)), 1.5);
+        assert_eq!(grid.sample(vec3(1.5, 1.0, 2.0)), 56.0);
+        assert_eq!(grid.sample(vec3(4.0, 2.0, 4.0)), 113.0);
+        assert_eq!(grid.sample(vec3(0.0, 0.0, 0.0)), 0.0);
+        assert_eq!(grid.max_value(), 113.0);
+    }
+
+    #[test]
+    fn is_zero_outside_the_grid() {
+        let grid = grid();
+        for &p in &[
+            vec3(-0.01, 1.0, 1.0),
+            vec3(4.01, 1.0, 1.0),
+            vec3(2.0, -0.01, 1.0),
+            vec3(2.0, 2.01, 1.0),
+            vec3(2.0, 1.0, -0.01),
+            vec3(2.0, 1.0, 4.01),
+        ] {
+            assert_eq!(grid.sample(p), 0.0);
+        }
+    }
+}